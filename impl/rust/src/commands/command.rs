@@ -1,3 +1,5 @@
+use error::Result;
+
 pub trait Command {
-    fn run(&mut self);
+    fn run(&mut self) -> Result<()>;
 }
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+use commands::command::Command;
+use commands::fetch_command::TRUST_STORE_FILE_NAME;
+use error::Result;
+use manifest;
+use options_parse::Options;
+use root::Root;
+
+/// Prints the directories and files the other commands use, one `NAME='value'` line each
+pub struct EnvCommand {
+    root: Root,
+    current_directory: PathBuf,
+    options: Options,
+}
+
+impl EnvCommand {
+    pub fn new(root: Root, current_directory: PathBuf, options: Options) -> EnvCommand {
+        EnvCommand { root: root, current_directory: current_directory, options: options }
+    }
+
+    /// Returns the printed variables in their order
+    pub fn variables(&self) -> Vec<(&'static str, String)> {
+        let manifest_path = self.current_directory.join(manifest::FILE_NAME);
+        let manifest_path = if manifest_path.is_file() { display(&manifest_path) } else {
+            String::new()
+        };
+        vec![("SB_ROOT", display(&self.root.directory)),
+             ("SB_ROOT_ORIGIN", self.root.origin.to_string()),
+             ("SB_SLICES", display(&self.root.slice_root_directory())),
+             ("SB_TRUST_STORE", display(&self.root.directory.join(TRUST_STORE_FILE_NAME))),
+             ("SB_MANIFEST", manifest_path),
+             ("SB_LOCKFILE", display(&self.current_directory.join(&self.options.lockfile)))]
+    }
+}
+
+impl Command for EnvCommand {
+    fn run(&mut self) -> Result<()> {
+        for (name, value) in self.variables() {
+            println!("{}='{}'", name, value.replace("'", "'\\''"));
+        }
+        Ok(())
+    }
+}
+
+fn display(path: &Path) -> String {
+    path.display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use EnvCommand;
+    use options_parse::Options;
+    use root::{Root, RootOrigin};
+
+    #[test]
+    fn variables_describe_root() {
+        let root = Root { directory: PathBuf::from("/data/sb"), origin: RootOrigin::UserData };
+        let command = EnvCommand::new(root, PathBuf::from("/nonexistent"), Options::new());
+        assert_eq!(command.variables(),
+                   vec![("SB_ROOT", String::from("/data/sb")),
+                        ("SB_ROOT_ORIGIN", String::from("user data directory")),
+                        ("SB_SLICES", String::from("/data/sb/slices")),
+                        ("SB_TRUST_STORE", String::from("/data/sb/trusted.gpg")),
+                        ("SB_MANIFEST", String::new()),
+                        ("SB_LOCKFILE", String::from("/nonexistent/sb.lock"))]);
+    }
+}
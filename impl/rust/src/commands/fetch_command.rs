@@ -1,124 +1,267 @@
-extern crate zip;
-use std::fs::{create_dir, create_dir_all, File};
-use std::io::{Cursor, Read, Write};
 use std::path::{Path, PathBuf};
-use curl::http;
-use rustc_serialize::json::Json;
-use zip::read::ZipArchive;
-use version;
+use ChecksumPolicy;
 use commands::command::Command;
+use error::Result;
+use options_parse::Options;
+use source;
+use source::Source;
+use source::{install, verify};
 
+/// Keyring of the keys which may sign fetched archives, relative to the root directory
+pub const TRUST_STORE_FILE_NAME: &'static str = "trusted.gpg";
+
+/// Downloads slices from the source selected by the url into the slice root directory
 pub struct FetchCommand<'a> {
     slice_root_directory: &'a Path,
+    trust_store: PathBuf,
+    checksum_policy: ChecksumPolicy,
+    source: Box<Source>,
 }
 
-/// # Panics
-/// Panics for empty versions
-fn choose_latest_version<'a>(versions: &'a Vec<&str>) -> &'a str {
-    assert_not_empty!(versions);
-    let iter = versions.iter().enumerate();
-    let (_, i) = iter.map(|(i, v)| {
-        let (_, version) = version::extract_name_and_version(v);
-        (version, i)
-                     })
-                     .max()
-                     .unwrap();
-    versions.iter().nth(i).unwrap()
+impl<'a> FetchCommand<'a> {
+    /// `options.url` selects the source as described by `source::from_url`
+    /// # Errors
+    /// `Error::Usage` if the url points to nothing a source can be created for
+    pub fn new(root_directory: &Path, slice_root_directory: &'a Path, options: &Options)
+               -> Result<FetchCommand<'a>> {
+        let source = try!(source::from_url(&options.url));
+        Ok(FetchCommand {
+            slice_root_directory: slice_root_directory,
+            trust_store: root_directory.join(TRUST_STORE_FILE_NAME),
+            checksum_policy: options.checksum_policy,
+            source: source,
+        })
+    }
 }
 
-impl<'a> FetchCommand<'a> {
-    pub fn new(slice_root_directory: &'a Path) -> FetchCommand {
-        FetchCommand { slice_root_directory: slice_root_directory }
-    }
-
-    fn execute_request_to_uri(uri: &str) -> Vec<u8> {
-        let mut handle = http::handle();
-        let request = handle.get(uri).header("user-agent", "Mozilla/4.0 (compatible)");
-        let response = request.exec().unwrap();
-        response.move_body()
-    }
-
-    fn determine_latest_version() -> String {
-        let body = FetchCommand::execute_request_to_uri("https://api.github.\
-                                                         com/repos/slicebuild/slices/branches");
-        let body = String::from_utf8(body).unwrap();
-        let json = Json::from_str(&body).unwrap();
-        let array = json.as_array().unwrap();
-        let iter = array.into_iter();
-        let versions = iter.map(|item| {
-            match item.as_object() {
-                Some(obj) => {
-                    let field = "name".to_string();
-                    match obj.get(&field) {
-                        Some(name) => {
-                            match name.as_string() {
-                                Some(name) => name,
-                                None => panic!("{} is not a string", field)
-                            }
-                        },
-                        None => panic!("Object has no \"{}\" field", field)
-                    }
+impl<'a> Command for FetchCommand<'a> {
+    fn run(&mut self) -> Result<()> {
+        println!("Fetching slices from {} source {}", self.source.kind(), self.source.location());
+        let fetched = try!(self.source.fetch());
+        let verification = try!(verify::verify(&*self.source, &fetched, self.checksum_policy,
+                                               &self.trust_store));
+        let names = try!(install::install(fetched, self.slice_root_directory));
+        match verification {
+            Some(verification) => {
+                let signature = if verification.signed { ", signature is trusted" } else { "" };
+                println!("Verified SHA-256 {}{}", verification.digest, signature);
+                for name in names {
+                    try!(verify::record(&verification, &*self.source,
+                                        &self.slice_root_directory.join(name)));
                 }
-                None => panic!("Expected object, but received {:?}", item)
-            }
-                           })
-                           .filter(|name| *name != "master")
-                           .collect::<Vec<_>>();
-        choose_latest_version(&versions).to_string()
-    }
-
-    fn download_latest_version() -> Vec<u8> {
-        let version = FetchCommand::determine_latest_version();
-        println!("Version = {}", version);
-        let uri = format!("https://codeload.github.com/slicebuild/slices/zip/{}",
-                          version);
-        FetchCommand::execute_request_to_uri(&uri)
-    }
-
-    fn extract_archive_into_directory(mut zip_archive: ZipArchive<Cursor<Vec<u8>>>,
-                                      path: PathBuf) {
-        for i in 0..zip_archive.len() {
-            let mut path = path.clone();
-            let mut file = zip_archive.by_index(i).unwrap();
-            {
-                let file_name = file.name();
-                path.push(file_name);
             }
-            if file.size() != 0 {
-                let _ = create_dir_all(path.parent().unwrap());
-                let mut bytes: Vec<u8> = Vec::with_capacity(file.size() as usize);
-                let _ = file.read_to_end(&mut bytes).unwrap();
-                FetchCommand::write_bytes_to_temporary_file(&bytes, &path);
+            None => {
+                for name in names {
+                    try!(verify::remove_record(&self.slice_root_directory.join(name)));
+                }
             }
         }
+        Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::{Cursor, Read, Write};
+    use std::net::TcpListener;
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use std::thread;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use tar;
+    use zip::{CompressionMethod, ZipWriter};
+    use zip::write::FileOptions;
+    use {ChecksumPolicy, Command, FetchCommand};
+    use error::Error;
+    use helper;
+    use options_parse::Options;
+
+    const SLICE_PATH: &'static str = "slices-1.0.0/_/debian-8";
+    const SLICE_CONTENT: &'static str = "FROM\ndebian:jessie\n";
 
-    fn write_bytes_to_temporary_file(bytes: &[u8], file_path: &PathBuf) {
-        let _ = create_dir(file_path.parent().unwrap());
-        let mut file = File::create(file_path).unwrap();
-        let _ = file.write(bytes);
+    fn create_zip() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file(SLICE_PATH, options).unwrap();
+        writer.write_all(SLICE_CONTENT.as_bytes()).unwrap();
+        writer.finish().unwrap().into_inner()
     }
-}
 
-impl<'a> Command for FetchCommand<'a> {
-    fn run(&mut self) {
-        let bytes = FetchCommand::download_latest_version();
-        let cursor = Cursor::new(bytes);
-        let zip_archive = ZipArchive::new(cursor).unwrap();
-        let directory = self.slice_root_directory.to_path_buf();
-        FetchCommand::extract_archive_into_directory(zip_archive, directory);
+    fn create_tarball() -> Vec<u8> {
+        let encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(SLICE_CONTENT.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, SLICE_PATH, SLICE_CONTENT.as_bytes()).unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn write_file(path: &Path, bytes: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(bytes).unwrap();
+    }
+
+    fn options(url: &str, checksum_policy: ChecksumPolicy) -> Options {
+        Options { url: String::from(url), checksum_policy: checksum_policy, .. Options::new() }
+    }
+
+    fn fetch(url: &str) -> PathBuf {
+        let slice_root_directory = helper::create_temporary_directory("sb-fetch").unwrap();
+        let options = options(url, ChecksumPolicy::Auto);
+        FetchCommand::new(&slice_root_directory, &slice_root_directory, &options).unwrap()
+                                                                          .run().unwrap();
+        slice_root_directory
+    }
+
+    fn assert_slice_is_fetched(slice_root_directory: &Path) {
+        let mut content = String::new();
+        File::open(slice_root_directory.join(SLICE_PATH)).unwrap()
+                                                        .read_to_string(&mut content).unwrap();
+        assert_eq!(content, SLICE_CONTENT);
+        fs::remove_dir_all(slice_root_directory).unwrap();
     }
-}
 
-#[cfg(test)]
-mod tests {
     #[test]
-    fn choose_latest_version() {
-        let versions = vec!["du-0.0.1-rc.1",
-                            "du-0.0.2",
-                            "my-du-1.0.0",
-                            "ubuntu-only-1.0.1",
-                            "fed-2.1.1"];
-        assert_eq!(super::choose_latest_version(&versions), "fed-2.1.1");
+    fn fetch_from_local_zip() {
+        let directory = helper::create_temporary_directory("sb-zip").unwrap();
+        let path = directory.join("slices.zip");
+        write_file(&path, &create_zip());
+        assert_slice_is_fetched(&fetch(path.to_str().unwrap()));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn fetch_records_digest_of_verified_zip() {
+        let directory = helper::create_temporary_directory("sb-zip").unwrap();
+        let path = directory.join("slices.zip");
+        let zip = create_zip();
+        write_file(&path, &zip);
+        let digest = helper::sha256_hex(&zip);
+        let manifest = format!("{}  slices.zip\n", digest);
+        write_file(&directory.join("slices.zip.sha256"), manifest.as_bytes());
+        let slice_root_directory = fetch(path.to_str().unwrap());
+        let mut record = String::new();
+        File::open(slice_root_directory.join("slices-1.0.0.sha256")).unwrap()
+                                                                    .read_to_string(&mut record)
+                                                                    .unwrap();
+        assert_eq!(record.split_whitespace().next(), Some(digest.as_str()));
+        assert_slice_is_fetched(&slice_root_directory);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn unverified_fetch_removes_recorded_digest() {
+        let directory = helper::create_temporary_directory("sb-zip").unwrap();
+        let path = directory.join("slices.zip");
+        let zip = create_zip();
+        write_file(&path, &zip);
+        let manifest_path = directory.join("slices.zip.sha256");
+        write_file(&manifest_path, helper::sha256_hex(&zip).as_bytes());
+        let slice_root_directory = fetch(path.to_str().unwrap());
+        let record_path = slice_root_directory.join("slices-1.0.0.sha256");
+        assert!(record_path.exists());
+        fs::remove_file(&manifest_path).unwrap();
+        let options = options(path.to_str().unwrap(), ChecksumPolicy::Auto);
+        FetchCommand::new(&slice_root_directory, &slice_root_directory, &options).unwrap()
+                                                                          .run().unwrap();
+        assert!(!record_path.exists());
+        assert_slice_is_fetched(&slice_root_directory);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn fetch_keeps_slice_root_if_checksum_does_not_match() {
+        let directory = helper::create_temporary_directory("sb-zip").unwrap();
+        let path = directory.join("slices.zip");
+        write_file(&path, &create_zip());
+        let checksum: String = (0..64).map(|_| '0').collect();
+        write_file(&directory.join("slices.zip.sha256"), checksum.as_bytes());
+        let slice_root_directory = directory.join("slices");
+        let options = options(path.to_str().unwrap(), ChecksumPolicy::Auto);
+        let result = FetchCommand::new(&directory, &slice_root_directory, &options).unwrap()
+                                                                                  .run();
+        match result {
+            Err(Error::Verification(..)) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(!slice_root_directory.exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn fetch_from_local_tarball() {
+        let directory = helper::create_temporary_directory("sb-tarball").unwrap();
+        let path = directory.join("slices.tar.gz");
+        write_file(&path, &create_tarball());
+        assert_slice_is_fetched(&fetch(&format!("file://{}", path.display())));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn fetch_from_local_directory() {
+        let directory = helper::create_temporary_directory("sb-directory").unwrap();
+        write_file(&directory.join(SLICE_PATH), SLICE_CONTENT.as_bytes());
+        assert_slice_is_fetched(&fetch(directory.to_str().unwrap()));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn fetch_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            // The archive is requested first, then its checksum manifest which does not exist
+            for &(status, ref body) in &[("200 OK", create_zip()), ("404 Not Found", Vec::new())] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let count = stream.read(&mut buffer).unwrap();
+                    request.extend_from_slice(&buffer[..count]);
+                }
+                write!(stream, "HTTP/1.0 {}\r\nContent-Length: {}\r\n\r\n", status,
+                       body.len()).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        assert_slice_is_fetched(&fetch(&format!("http://{}/slices.zip", address)));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn fetch_from_git_repository() {
+        let git_is_available = process::Command::new("git").arg("--version").output()
+                                                .map(|o| o.status.success()).unwrap_or(false);
+        if !git_is_available {
+            return;
+        }
+        let repository = helper::create_temporary_directory("sb-repository").unwrap();
+        write_file(&repository.join("_/debian-8"), SLICE_CONTENT.as_bytes());
+        let git = |arguments: &[&str]| {
+            let status = process::Command::new("git").args(arguments).current_dir(&repository)
+                                                     .env("GIT_AUTHOR_NAME", "sb")
+                                                     .env("GIT_AUTHOR_EMAIL", "sb@localhost")
+                                                     .env("GIT_COMMITTER_NAME", "sb")
+                                                     .env("GIT_COMMITTER_EMAIL", "sb@localhost")
+                                                     .output().unwrap().status;
+            assert!(status.success(), "git {:?} failed", arguments);
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "Slices"]);
+        git(&["tag", "1.0.0"]);
+        let repository_name = repository.file_name().unwrap().to_str().unwrap().to_string();
+        let slice_root_directory = fetch(&format!("git+file://{}#1.0.0", repository.display()));
+        let slice_path = slice_root_directory.join(format!("{}-1.0.0/_/debian-8",
+                                                           repository_name));
+        assert!(slice_path.is_file());
+        assert!(!slice_path.parent().unwrap().parent().unwrap().join(".git").exists());
+        fs::remove_dir_all(&slice_root_directory).unwrap();
+        fs::remove_dir_all(&repository).unwrap();
     }
 }
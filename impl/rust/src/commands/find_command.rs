@@ -1,22 +1,84 @@
 use std::path::Path;
 use commands::command::Command;
-use {RequestedSlice, VersionMatchStrategy};
+use error::Result;
+use helper;
+use RequestedSlice;
+use options_parse::{Options, OutputFormat};
 use os::Os;
-use slice::List;
+use report::{FindReport, RequestedEntry, SliceEntry, UnresolvedDependency};
+use slice::{List, Slice};
 
 pub struct FindCommand<'a> {
     slices: Vec<RequestedSlice>,
     os: Os,
     slice_root_directory: &'a Path,
+    options: Options,
 }
 
 impl<'a> FindCommand<'a> {
     /// # Panics
     /// if `slices` is empty
-    pub fn new(slices: Vec<RequestedSlice>, os: Os, slice_root_directory: &'a Path)
-               -> FindCommand<'a> {
+    pub fn new(slices: Vec<RequestedSlice>, os: Os, slice_root_directory: &'a Path,
+               options: Options) -> FindCommand<'a> {
         assert_not_empty!(slices);
-        FindCommand { slices: slices, os: os, slice_root_directory: slice_root_directory }
+        FindCommand { slices: slices, os: os, slice_root_directory: slice_root_directory,
+                      options: options }
+    }
+
+    /// Searches the requested slices and returns the result instead of printing it
+    pub fn report(&self) -> Result<FindReport> {
+        let list = try!(self.load_list());
+        Ok(self.create_report(&list))
+    }
+
+    fn load_list(&self) -> Result<List> {
+        List::new(&self.slice_root_directory, &self.os, self.options.duplicate_policy,
+                  self.options.prerelease_policy)
+    }
+
+    fn create_report(&self, list: &List) -> FindReport {
+        let mut report = FindReport { found: Vec::new(), similar: Vec::new(),
+                                      missing_requested: Vec::new(),
+                                      skipped_prereleases: Vec::new(),
+                                      unresolved_dependencies: Vec::new() };
+        for requested in &self.slices {
+            let found = list.find_slice(&requested.name, &requested.version,
+                                        requested.version_match_strategy);
+            match found {
+                Some(slice) => report.found.push(create_slice_entry(slice)),
+                None => {
+                    report.missing_requested.push(RequestedEntry {
+                        name: requested.name.clone(),
+                        version: requested.version.to_string(),
+                    })
+                }
+            }
+            for slice in list.skipped_prereleases(&requested.name, &requested.version,
+                                                  requested.version_match_strategy) {
+                report.skipped_prereleases.push(create_slice_entry(slice));
+            }
+            for slice in list.find_similar_slices(&requested.name) {
+                let entry = create_slice_entry(slice);
+                if !report.similar.contains(&entry) {
+                    report.similar.push(entry);
+                }
+            }
+        }
+        for slice in list.slices() {
+            for dependency in slice.unresolved_dependencies() {
+                report.unresolved_dependencies.push(UnresolvedDependency {
+                    dependency: dependency.clone(),
+                    slice: create_slice_entry(slice),
+                });
+                for prerelease in list.skipped_dependency_prereleases(dependency) {
+                    let entry = create_slice_entry(prerelease);
+                    if !report.skipped_prereleases.contains(&entry) {
+                        report.skipped_prereleases.push(entry);
+                    }
+                }
+            }
+        }
+        report
     }
 
     fn print_found_requested_slices(&self, list: &List) {
@@ -70,6 +132,23 @@ impl<'a> FindCommand<'a> {
         println!("");
     }
 
+    fn print_skipped_prereleases(&self, list: &List) {
+        let slices = self.slices.iter()
+                                .flat_map(|s| {
+                                    list.skipped_prereleases(&s.name, &s.version,
+                                                             s.version_match_strategy)
+                                })
+                                .collect::<Vec<_>>();
+        if slices.is_empty() {
+            return;
+        }
+        println!("Skipped prereleases, request a prerelease version or use --pre:");
+        for slice in slices {
+            println!("{}-{} from {}", slice.name(), slice.version(), slice.bunch());
+        }
+        println!("");
+    }
+
     fn print_missing_requested_slices(&self, list: &List) {
         let iter = self.slices.iter();
         let slices = iter.map(|s| &s.name)
@@ -89,16 +168,72 @@ impl<'a> FindCommand<'a> {
 }
 
 impl<'a> Command for FindCommand<'a> {
-    fn run(&mut self) {
-        match List::new(&self.slice_root_directory, &self.os,
-                        VersionMatchStrategy::ExactOrGreater) {
-            Ok(list) => {
+    fn run(&mut self) -> Result<()> {
+        let list = try!(self.load_list());
+        helper::print_diagnostics(&list);
+        match self.options.output {
+            OutputFormat::Text => {
                 self.print_similar_slices(&list);
                 self.print_missing_dependencies(&list);
                 self.print_found_requested_slices(&list);
+                self.print_skipped_prereleases(&list);
                 self.print_missing_requested_slices(&list);
             }
-            Err(error) => println!("{}", error)
+            OutputFormat::Json => print!("{}", self.create_report(&list).to_json()),
+            OutputFormat::Yaml => print!("{}", self.create_report(&list).to_yaml()),
         }
+        Ok(())
+    }
+}
+
+fn create_slice_entry(slice: &Slice) -> SliceEntry {
+    SliceEntry { name: slice.name().clone(), version: slice.version().to_string(),
+                 bunch: slice.bunch().to_string(), path: slice.path().display().to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use {FindCommand, PrereleasePolicy, RequestedSlice, VersionMatchStrategy};
+    use error::Result;
+    use options_parse::Options;
+    use os::Os;
+    use report::FindReport;
+    use version;
+
+    fn create_report(names: Vec<&str>, options: Options) -> Result<FindReport> {
+        let requested = names.into_iter().map(|name| {
+            RequestedSlice { name: String::from(name), version: version::zero(),
+                             version_match_strategy: VersionMatchStrategy::ExactOrGreater }
+        })
+        .collect();
+        let os = Os::parse("debian").unwrap();
+        let mut path = env::current_dir().expect("Current dir is not set").to_path_buf();
+        path.push("test_slices");
+        FindCommand::new(requested, os, &path, options).report()
+    }
+
+    #[test]
+    fn report_for_jekyll_and_missing_slice() {
+        let options = Options { prerelease_policy: PrereleasePolicy::Include, .. Options::new() };
+        let report = create_report(vec!["jekyll", "nonexistent"], options).unwrap();
+
+        assert_eq!(report.found.len(), 1);
+        let jekyll = &report.found[0];
+        assert_eq!(jekyll.name, "jekyll");
+        assert_eq!(jekyll.bunch, "slices-du-0.0.2");
+        assert!(jekyll.path.ends_with("jekyll-3.0.0-beta.9"));
+        assert!(report.similar.contains(jekyll));
+        assert_eq!(report.missing_requested.len(), 1);
+        assert_eq!(report.missing_requested[0].name, "nonexistent");
+    }
+
+    #[test]
+    fn report_shows_skipped_prerelease() {
+        let report = create_report(vec!["jekyll"], Options::new()).unwrap();
+        assert!(report.found.is_empty());
+        assert_eq!(report.missing_requested[0].name, "jekyll");
+        assert_eq!(report.skipped_prereleases.len(), 1);
+        assert_eq!(report.skipped_prereleases[0].version, "3.0.0-beta.9");
     }
 }
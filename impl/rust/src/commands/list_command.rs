@@ -0,0 +1,242 @@
+use std::path::Path;
+use PrereleasePolicy;
+use commands::command::Command;
+use error::Result;
+use helper;
+use options_parse::Options;
+use os::Os;
+use slice::{Bunch, List, Slice};
+
+/// Prints every slice grouped by bunch, with all its versions, dependencies and paths
+pub struct ListCommand<'a> {
+    /// OS the listing is limited to, every OS the bunches support if `None`
+    os: Option<Os>,
+    slice_root_directory: &'a Path,
+    options: Options,
+}
+
+/// Bunch in the listing with the OS slices it supports and its slices
+struct BunchGroup<'b> {
+    bunch: &'b Bunch,
+    os_slices: Vec<&'b Slice>,
+    slices: Vec<&'b Slice>,
+}
+
+impl<'a> ListCommand<'a> {
+    pub fn new(os: Option<Os>, slice_root_directory: &'a Path, options: Options)
+               -> ListCommand<'a> {
+        ListCommand { os: os, slice_root_directory: slice_root_directory, options: options }
+    }
+
+    /// Returns the listing instead of printing it
+    pub fn render(&self) -> Result<String> {
+        let lists = try!(self.load_lists());
+        Ok(self.render_lists(&lists))
+    }
+
+    /// Loads a list for the requested OS or one list for every OS the bunches support.
+    /// Prereleases are listed like releases.
+    fn load_lists(&self) -> Result<Vec<List>> {
+        let oses = match self.os {
+            Some(ref os) => vec![os.clone()],
+            None => {
+                let mut oses = Vec::new();
+                for name in try!(List::os_names(self.slice_root_directory)) {
+                    oses.push(try!(Os::parse(&name)));
+                }
+                oses
+            }
+        };
+        let mut lists = Vec::with_capacity(oses.len());
+        for os in &oses {
+            lists.push(try!(List::new(self.slice_root_directory, os,
+                                      self.options.duplicate_policy,
+                                      PrereleasePolicy::Include)));
+        }
+        Ok(lists)
+    }
+
+    fn render_lists(&self, lists: &[List]) -> String {
+        let groups = self.group_by_bunch(lists);
+        if groups.is_empty() {
+            return String::from("No slices\n");
+        }
+        let mut text = String::new();
+        for group in groups {
+            let os_slices = group.os_slices.iter()
+                                           .map(|s| format!("{}-{}", s.name(), s.version()))
+                                           .collect::<Vec<_>>();
+            text.push_str(&format!("{} (OS: {})\n", group.bunch, os_slices.join(", ")));
+            let mut previous_name: Option<&String> = None;
+            for slice in group.slices {
+                if previous_name != Some(slice.name()) {
+                    text.push_str(&format!("  {}\n", slice.name()));
+                    previous_name = Some(slice.name());
+                }
+                text.push_str(&format!("    {}  {}\n", slice.version(), slice.path().display()));
+                if self.options.tree {
+                    push_dependency_tree(&mut text, slice, "      ");
+                } else if !slice.dependencies().is_empty() {
+                    let dependencies = slice.dependencies().iter()
+                                                           .map(ToString::to_string)
+                                                           .collect::<Vec<_>>();
+                    text.push_str(&format!("      depends on: {}\n", dependencies.join(", ")));
+                }
+            }
+        }
+        text
+    }
+
+    /// Groups the slices which pass the filters by bunch, the latest bunch first. Slices of a
+    /// bunch are sorted by name, then by version DESC.
+    fn group_by_bunch<'b>(&self, lists: &'b [List]) -> Vec<BunchGroup<'b>> {
+        let mut groups: Vec<BunchGroup<'b>> = Vec::new();
+        for list in lists {
+            for os_slice in list.os_slices() {
+                let group = group_of(&mut groups, os_slice.bunch());
+                if !group.os_slices.iter().any(|s| s.path() == os_slice.path()) {
+                    group.os_slices.push(os_slice);
+                }
+            }
+            for slice in list.slices() {
+                if !self.is_listed(slice) {
+                    continue;
+                }
+                let group = group_of(&mut groups, slice.bunch());
+                if !group.slices.iter().any(|s| s.path() == slice.path()) {
+                    group.slices.push(slice);
+                }
+            }
+        }
+        groups.retain(|group| {
+            let bunch = &self.options.bunch;
+            let bunch_is_listed = bunch.is_empty() || *bunch == group.bunch.name ||
+                                  *bunch == group.bunch.to_string();
+            bunch_is_listed && (self.options.name_pattern.is_empty() || !group.slices.is_empty())
+        });
+        let by_name_then_latest = |a: &&Slice, b: &&Slice| {
+            a.name().cmp(b.name()).then(b.version().cmp(a.version()))
+        };
+        for group in &mut groups {
+            group.os_slices.sort_by(&by_name_then_latest);
+            group.slices.sort_by(&by_name_then_latest);
+        }
+        groups.sort_by(|a, b| {
+            b.bunch.version.cmp(&a.bunch.version).then(a.bunch.name.cmp(&b.bunch.name))
+        });
+        groups
+    }
+
+    fn is_listed(&self, slice: &Slice) -> bool {
+        self.options.name_pattern.is_empty() ||
+        helper::glob_matches(&self.options.name_pattern, slice.name())
+    }
+}
+
+impl<'a> Command for ListCommand<'a> {
+    fn run(&mut self) -> Result<()> {
+        let lists = try!(self.load_lists());
+        helper::print_unique_diagnostics(&lists);
+        print!("{}", self.render_lists(&lists));
+        Ok(())
+    }
+}
+
+fn group_of<'c, 'b>(groups: &'c mut Vec<BunchGroup<'b>>, bunch: &'b Bunch)
+                    -> &'c mut BunchGroup<'b> {
+    match groups.iter().position(|group| group.bunch == bunch) {
+        Some(index) => &mut groups[index],
+        None => {
+            groups.push(BunchGroup { bunch: bunch, os_slices: Vec::new(), slices: Vec::new() });
+            groups.last_mut().unwrap()
+        }
+    }
+}
+
+/// Appends the dependency closure of `slice` as an ASCII tree, missing dependencies included
+fn push_dependency_tree(text: &mut String, slice: &Slice, prefix: &str) {
+    let mut children = slice.resolved_dependencies()
+                            .into_iter()
+                            .map(|s| (format!("{}-{}", s.name(), s.version()), Some(s)))
+                            .collect::<Vec<_>>();
+    children.extend(slice.unresolved_dependencies()
+                         .iter()
+                         .map(|dependency| (format!("{} (missing)", dependency), None)));
+    let count = children.len();
+    for (index, (label, child)) in children.into_iter().enumerate() {
+        let is_last = index + 1 == count;
+        text.push_str(&format!("{}{}{}\n", prefix, if is_last { "`-- " } else { "|-- " },
+                               label));
+        if let Some(child) = child {
+            let child_prefix = format!("{}{}", prefix, if is_last { "    " } else { "|   " });
+            push_dependency_tree(text, child, &child_prefix);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use ListCommand;
+    use options_parse::Options;
+    use os::Os;
+
+    fn render(os: Option<&str>, options: Options) -> String {
+        let os = os.map(|os| Os::parse(os).unwrap());
+        let mut path = env::current_dir().expect("Current dir is not set").to_path_buf();
+        path.push("test_slices");
+        ListCommand::new(os, &path, options).render().unwrap()
+    }
+
+    fn slices_path(relative_path: &str) -> String {
+        let mut path = env::current_dir().expect("Current dir is not set").to_path_buf();
+        path.push("test_slices/slices-du-0.0.2");
+        path.push(relative_path);
+        path.display().to_string()
+    }
+
+    #[test]
+    fn list_shows_versions_dependencies_and_paths() {
+        let options = Options { name_pattern: String::from("ruby-1*"), .. Options::new() };
+        assert_eq!(render(Some("debian"), options.clone()), "No slices\n");
+        let options = Options { name_pattern: String::from("ruby"), .. options };
+        assert_eq!(render(Some("debian"), options),
+                   format!("slices-du-0.0.2 (OS: debian-8.2.0)
+  ruby
+    2.2.3  {}
+      depends on: wget
+    1.9.1  {}
+      depends on: update
+",
+                           slices_path("r/ruby/ruby-2.2.3"), slices_path("r/ruby/ruby-1.9.1")));
+    }
+
+    #[test]
+    fn list_without_os_shows_every_supported_os() {
+        let options = Options { name_pattern: String::from("jekyll"), .. Options::new() };
+        let text = render(None, options);
+        assert!(text.starts_with("slices-du-0.0.2 (OS: debian-8.2.0, ubuntu-14.3.2)\n"), text);
+        assert!(text.contains("  jekyll\n    3.0.0-beta.9  "), text);
+    }
+
+    #[test]
+    fn list_prints_dependency_tree() {
+        let options = Options { name_pattern: String::from("jekyll"), tree: true,
+                                .. Options::new() };
+        let text = render(Some("debian"), options);
+        assert!(text.ends_with("      `-- ruby-2.2.3
+          `-- wget-0.0.0
+              `-- update-0.0.0
+"),
+                text);
+    }
+
+    #[test]
+    fn list_filters_by_bunch() {
+        let options = Options { bunch: String::from("slices-du"),
+                                name_pattern: String::from("bc"), .. Options::new() };
+        assert!(render(Some("debian"), options.clone()).starts_with("slices-du-0.0.2 "));
+        let options = Options { bunch: String::from("slices-1.0.0"), .. options };
+        assert_eq!(render(Some("debian"), options), "No slices\n");
+    }
+}
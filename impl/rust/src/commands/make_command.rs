@@ -4,24 +4,31 @@ use std::fs;
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use {RequestedSlice, VersionMatchStrategy};
+use {LockMode, RequestedSlice};
 use commands::command::Command;
-use formatters;
+use error::{Error, Result};
+use formatters::Registry;
+use helper;
+use lock::Lockfile;
 use options_parse::Options;
 use os::Os;
+use plan::Plan;
 use slice::{List, Slice};
+use version;
 
 pub struct MakeCommand<'a> {
     slices: Vec<RequestedSlice>,
     os: Os,
     root_directory: &'a Path,
     slice_root_directory: &'a Path,
-    options: Options
+    options: Options,
+    formatters: Registry,
 }
 
 struct DependenciesSearchResult<'a> {
     found_requested_slices: Vec<&'a Slice>,
-    missing_requested_slices: Vec<&'a str>,
+    /// Names of requested slices which are not found, with the skipped prerelease if any
+    missing_requested_slices: Vec<String>,
     unresolved_dependencies: HashMap<&'a str, Vec<&'a str>>,
 }
 
@@ -34,25 +41,12 @@ impl<'a> MakeCommand<'a> {
         assert!(!slices.is_empty(), "There is no specified layers");
         MakeCommand { slices: slices, os: os, root_directory: root_directory,
                       slice_root_directory: slice_root_directory,
-                      options: options }
+                      options: options, formatters: Registry::new() }
     }
 
-    fn create_code_from_slices(&self, slices: Vec<&Slice>, visited_slices: &mut Vec<&str>) -> String {
-        let mut code = String::new();
-        for slice in slices {
-            let slice_name = slice.name() as &str;
-            if visited_slices.contains(&slice_name) {
-                continue;
-            }
-            let dependencies = slice.resolved_dependencies();
-            if !dependencies.is_empty() {
-                let dependency_code = self.create_code_from_slices(dependencies, visited_slices);
-                code.push_str(&dependency_code);
-            }
-            let slice_code = formatters::generate_code(&slice, &self.options.format);
-            code.push_str(&slice_code);
-        }
-        code
+    /// Formatters `Options::format` is looked up in, custom formatters are registered here
+    pub fn formatters_mut(&mut self) -> &mut Registry {
+        &mut self.formatters
     }
 
     fn get_output_file_path(&self) -> PathBuf {
@@ -85,109 +79,301 @@ impl<'a> MakeCommand<'a> {
                     result.found_requested_slices.push(slice.borrow());
                 }
             } else {
-                result.missing_requested_slices.push(&slice.name);
+                let skipped = list.skipped_prereleases(&slice.name, &slice.version,
+                                                       slice.version_match_strategy);
+                let missing_slice = match skipped.first() {
+                    Some(prerelease) => {
+                        format!("{} (prerelease {}-{} is skipped, request it or use --pre)",
+                                slice.name, prerelease.name(), prerelease.version())
+                    }
+                    None => slice.name.clone(),
+                };
+                result.missing_requested_slices.push(missing_slice);
             }
         }
         result
     }
 
-    fn generate_code(&self) -> String {
-        match List::new(&self.slice_root_directory, &self.os,
-                        VersionMatchStrategy::ExactOrGreater) {
-            Ok(list) => {
-                match self.generate_code_for_list(&list) {
-                    Some(code) => code,
-                    None => panic!("Code generation failed")
-                }
+    /// Resolves the requested slices to the plan the code is generated from. Writes the
+    /// lockfile if `--lock` is given.
+    /// # Errors
+    /// `Error::Resolution` if a requested slice or a dependency is missing, or the lockfile
+    /// does not match
+    pub fn plan(&self) -> Result<Plan> {
+        let list = try!(List::new(&self.slice_root_directory, &self.os,
+                                  self.options.duplicate_policy,
+                                  self.options.prerelease_policy));
+        helper::print_diagnostics(&list);
+        let slices = match self.options.lock_mode {
+            LockMode::Ignore => try!(self.resolve_slices(&list)),
+            LockMode::Write => {
+                let slices = try!(self.resolve_slices(&list));
+                let lockfile = try!(Lockfile::from_slices(slices[0], &slices[1..]));
+                try!(lockfile.write(Path::new(&self.options.lockfile)));
+                slices
             }
-            Err(error) => panic!("{}", error)
-        }
+            LockMode::Locked => try!(self.resolve_locked_slices(&list)),
+        };
+        let requested_names = self.slices.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        Plan::new(slices[0], &slices[1..], &requested_names)
+    }
+
+    fn generate_code(&self) -> Result<String> {
+        let formatter = try!(self.formatters.find(&self.options.format));
+        let plan = try!(self.plan());
+        Ok(formatter.generate_code(&plan))
     }
 
-    fn generate_code_for_list(&self, list: &List) -> Option<String> {
+    /// Returns the OS slice followed by the requested slices and their dependencies in
+    /// dependency order
+    fn resolve_slices<'b>(&'b self, list: &'b List) -> Result<Vec<&'b Slice>> {
         let result = self.find_unresolved_dependencies(list);
         let has_missing_slice = !result.unresolved_dependencies.is_empty() ||
                                 !result.missing_requested_slices.is_empty();
         if has_missing_slice {
+            let mut message = String::from("Code generation failed");
             if !result.missing_requested_slices.is_empty() {
-                println!("Missing requested slices:");
+                message.push_str("\nMissing requested slices:");
                 for slice in &result.missing_requested_slices {
-                    println!("{}", slice);
+                    message.push_str(&format!("\n{}", slice));
                 }
             }
             if !result.unresolved_dependencies.is_empty() {
-                println!("Missing dependencies:");
+                message.push_str("\nMissing dependencies:");
                 for (requested_slice, missing_dependencies) in &result.unresolved_dependencies {
                     for missing_dep in missing_dependencies {
-                        println!("{} depends on {}, but it is missing", requested_slice, missing_dep);
+                        message.push_str(&format!("\n{} depends on {}, but it is missing",
+                                                  requested_slice, missing_dep));
+                        let skipped = list.skipped_dependency_prereleases(missing_dep);
+                        if let Some(prerelease) = skipped.first() {
+                            message.push_str(&format!(" (prerelease {}-{} is skipped, use \
+                                                       --pre)",
+                                                      prerelease.name(), prerelease.version()));
+                        }
                     }
                 }
             }
-            return None;
+            return Err(Error::Resolution(message));
         }
 
-        let mut visited_slices = Vec::new();
-        Some(self.create_code_from_slices(result.found_requested_slices, &mut visited_slices))
+        let os_slice = try!(list.os_slice().ok_or_else(|| {
+            Error::Resolution(format!("There is no OS slice for OS = {}-{}", self.os.name,
+                                      self.os.version))
+        }));
+        let mut slices = vec![os_slice];
+        slices.append(&mut try!(list.dependency_order(&result.found_requested_slices)));
+        Ok(slices)
     }
 
-    fn write_code(&self, code: String) {
-    	let path = self.get_output_file_path();
-    	if let Err(error) = fs::create_dir_all(path.parent().unwrap()) {
-    	    panic!("Directory for make file cannot be created.
-Path = {}
-Reason = {}",
-    	           path.parent().unwrap().display(), error);
-    	}
-        match File::create(path) {
-            Ok(mut file) => {
-                if let Err(error) = file.write_fmt(format_args!("{}", code)) {
-                    panic!("{}", error);
+    /// Returns the requested slices and their dependencies as recorded in the lockfile, the OS
+    /// slice first
+    /// # Errors
+    /// `Error::Resolution` if the lockfile is for another OS, does not contain a requested
+    /// slice with a matching version, or a locked slice is missing or changed
+    fn resolve_locked_slices<'b>(&self, list: &'b List) -> Result<Vec<&'b Slice>> {
+        let path = Path::new(&self.options.lockfile);
+        let lockfile = try!(Lockfile::read(path));
+        if lockfile.os.name != self.os.name {
+            return Err(Error::Resolution(format!("Lockfile {} is for OS = {}, not {}",
+                                                 path.display(), lockfile.os.name,
+                                                 self.os.name)));
+        }
+        for requested_slice in &self.slices {
+            let locked_slice = lockfile.slices.iter().find(|s| s.name == requested_slice.name);
+            let is_matched = locked_slice.map_or(false, |s| {
+                version::matches(&s.version, &requested_slice.version,
+                                 requested_slice.version_match_strategy)
+            });
+            if !is_matched {
+                let locked_version = locked_slice.map_or(String::new(), |s| {
+                    format!(", it has version {}", s.version)
+                });
+                return Err(Error::Resolution(format!("Lockfile {} does not contain requested \
+                                                      slice {} version {}{}. Run make with \
+                                                      --lock to update it",
+                                                     path.display(), requested_slice.name,
+                                                     requested_slice.version, locked_version)));
+            }
+        }
+        let slices = try!(lockfile.resolve(list));
+        let (os_slice, locked_slices) = (slices[0], &slices[1..]);
+        // Names of the requested slices and of their locked dependencies
+        let mut names: Vec<&str> = self.slices.iter().map(|s| s.name.as_str()).collect();
+        let mut index = 0;
+        while index < names.len() {
+            if let Some(slice) = locked_slices.iter().find(|s| s.name() == names[index]) {
+                for dependency in slice.dependencies() {
+                    if !names.contains(&dependency.name().as_str()) {
+                        names.push(dependency.name());
+                    }
                 }
             }
-            Err(error) =>  panic!("File cannot be created at path {}\nReason = {}",
-                                  self.get_output_file_path().display(), error)
+            index += 1;
         }
+        let mut needed_slices = vec![os_slice];
+        needed_slices.extend(locked_slices.iter().filter(|s| names.contains(&s.name().as_str())));
+        Ok(needed_slices)
+    }
+
+    fn write_code(&self, code: String) -> Result<()> {
+    	let path = self.get_output_file_path();
+    	if let Some(parent) = path.parent() {
+    	    try!(fs::create_dir_all(parent).map_err(|e| Error::io(parent, e)));
+    	}
+        let mut file = try!(File::create(&path).map_err(|e| Error::io(&path, e)));
+        file.write_all(code.as_bytes()).map_err(|e| Error::io(&path, e))
     }
 }
 
 impl<'a> Command for MakeCommand<'a> {
-    fn run(&mut self) {
-        let code = self.generate_code();
-        self.write_code(code);
+    fn run(&mut self) -> Result<()> {
+        let code = try!(self.generate_code());
+        self.write_code(code)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::env;
-    use std::path::Path;
-    use {MakeCommand, RequestedSlice, VersionMatchStrategy};
-    use options_parse::{Format, Options};
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use {LockMode, MakeCommand, PrereleasePolicy, RequestedSlice};
+    use error::{Error, Result};
+    use helper;
+    use options_parse::Options;
     use os::Os;
-    use version;
+    use plan::{Plan, Reason};
 
-    fn generate_code_for_jekyll_with_format(format: Format) -> String {
-        let version_match_strategy = VersionMatchStrategy::ExactOrGreater;
-        let slice = RequestedSlice { name: String::from("jekyll"), version: version::zero(),
-                                     version_match_strategy: version_match_strategy };
-        let slices = vec![slice];
-
-        let os = Os { name: String::from("debian"), version: version::zero() };
+    fn create_command<'a>(names: &[&str], slice_root_directory: &'a Path, options: Options)
+                          -> MakeCommand<'a> {
+        let slices = names.iter().map(|name| RequestedSlice::parse(name).unwrap()).collect();
+        let os = Os::parse("debian").unwrap();
+        MakeCommand::new(slices, os, Path::new("/"), slice_root_directory, options)
+    }
 
+    fn test_slices_path() -> PathBuf {
         let mut path = env::current_dir().expect("Current dir is not set").to_path_buf();
         path.push("test_slices");
+        path
+    }
+
+    fn generate_code(name: &str, options: Options) -> Result<String> {
+        create_command(&[name], &test_slices_path(), options).generate_code()
+    }
+
+    #[test]
+    fn plan_has_shared_dependency_once() {
+        let options = Options { prerelease_policy: PrereleasePolicy::Include,
+                                .. Options::new() };
+        let path = test_slices_path();
+        let plan = create_command(&["jekyll", "wget"], &path, options).plan().unwrap();
+        let steps = plan.steps().iter().map(|s| (s.name.as_str(), s.reason)).collect::<Vec<_>>();
+        assert_eq!(steps, vec![("debian", Reason::Os), ("update", Reason::Dependency),
+                               ("wget", Reason::Requested), ("ruby", Reason::Dependency),
+                               ("jekyll", Reason::Requested)]);
+    }
+
+    fn generate_code_for_jekyll_with_format(format: &str) -> String {
+        let options = Options { format: String::from(format),
+                                prerelease_policy: PrereleasePolicy::Include, .. Options::new() };
+        generate_code("jekyll", options).unwrap()
+    }
+
+    #[test]
+    fn unknown_format_is_usage_error() {
+        let options = Options { format: String::from("xml"), .. Options::new() };
+        match generate_code("bc", options) {
+            Err(Error::Usage(ref message)) => {
+                assert!(message.contains("[d, make, sh, "), "{}", message)
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn prerelease_is_not_made_without_pre() {
+        match generate_code("jekyll", Options::new()) {
+            Err(Error::Resolution(ref message)) => {
+                assert!(message.contains("jekyll-3.0.0-beta.9 is skipped"), "{}", message)
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn locked_make_reproduces_locked_resolution() {
+        let directory = helper::create_temporary_directory("sb-lock").unwrap();
+        let lockfile = directory.join("sb.lock").to_str().unwrap().to_string();
+        let options = Options { lock_mode: LockMode::Write, lockfile: lockfile,
+                                prerelease_policy: PrereleasePolicy::Include, .. Options::new() };
+        let code = generate_code("jekyll", options.clone()).unwrap();
+        let locked_options = Options { lock_mode: LockMode::Locked, .. options };
+        assert_eq!(generate_code("jekyll", locked_options.clone()).unwrap(), code);
+        match generate_code("bc", locked_options) {
+            Err(Error::Resolution(ref message)) => assert!(message.contains("--lock")),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn locked_make_emits_only_requested_slices_with_matching_versions() {
+        let directory = helper::create_temporary_directory("sb-lock-subset").unwrap();
+        let lockfile = directory.join("sb.lock").to_str().unwrap().to_string();
+        let options = Options { lock_mode: LockMode::Write, lockfile: lockfile,
+                                prerelease_policy: PrereleasePolicy::Include, .. Options::new() };
+        let path = test_slices_path();
+        create_command(&["jekyll", "bc"], &path, options.clone()).plan().unwrap();
+        let locked_options = Options { lock_mode: LockMode::Locked, .. options };
+        let plan = create_command(&["bc"], &path, locked_options.clone()).plan().unwrap();
+        let names = plan.steps().iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["debian", "update", "bc"]);
+        match create_command(&["ruby>=2.3"], &path, locked_options).plan() {
+            Err(Error::Resolution(ref message)) => assert!(message.contains("2.2.3")),
+            result => panic!("Unexpected result {:?}", result.map(|_| ())),
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
 
-        let options = Options { format: format, .. Options::new() };
+    fn write_slice(directory: &Path, file_name: &str, content: &str) {
+        let mut file = File::create(directory.join(file_name)).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
 
-        let command = MakeCommand::new(slices, os, Path::new("/"), &path, options);
-        println!("{}", command.generate_code());
-        command.generate_code()
+    fn ruby_dependencies_of_jekyll(plan: &Plan) -> Vec<String> {
+        plan.steps().iter().find(|s| s.name == "jekyll").unwrap().dependencies.clone()
+    }
+
+    #[test]
+    fn steps_depend_on_planned_versions() {
+        let root = helper::create_temporary_directory("sb-planned-versions").unwrap();
+        let bunch = root.join("slices-0.0.1");
+        fs::create_dir_all(bunch.join("_")).unwrap();
+        write_slice(&bunch.join("_"), "debian-8.2", "FROM\ndebian:jessie\n");
+        write_slice(&bunch, "ruby-2.2.3", "RUN\necho ruby\n");
+        write_slice(&bunch, "rails-4", "RUN\necho rails\nDEP\nruby <3\n");
+        write_slice(&bunch, "jekyll-3", "RUN\necho jekyll\nDEP\nruby\n");
+        let lockfile = root.join("sb.lock").to_str().unwrap().to_string();
+        let options = Options { lock_mode: LockMode::Write, lockfile: lockfile, .. Options::new() };
+        create_command(&["jekyll"], &root, options.clone()).plan().unwrap();
+        // Resolved on its own jekyll would get ruby 3
+        write_slice(&bunch, "ruby-3.0.0", "RUN\necho ruby\n");
+        let plan = create_command(&["jekyll", "rails"], &root, Options::new()).plan().unwrap();
+        assert_eq!(ruby_dependencies_of_jekyll(&plan), vec![String::from("ruby-2.2.3")]);
+        let locked_options = Options { lock_mode: LockMode::Locked, .. options };
+        let plan = create_command(&["jekyll"], &root, locked_options).plan().unwrap();
+        assert_eq!(ruby_dependencies_of_jekyll(&plan), vec![String::from("ruby-2.2.3")]);
+        fs::remove_dir_all(&root).unwrap();
     }
 
     #[test]
     fn generate_code_for_jekyll_in_docker_format() {
-        let code = generate_code_for_jekyll_with_format(Format::Docker);
-        assert_eq!(code, "RUN export DEBIAN_FRONTEND=noninteractive && \\
+        let code = generate_code_for_jekyll_with_format("d");
+        assert_eq!(code, "FROM debian:jessie
+
+RUN export DEBIAN_FRONTEND=noninteractive && \\
 apt-get update -q -y && \\
 alias apt-get='apt-get --no-install-recommends' && \\
 apt-get install -q -y apt-utils
@@ -211,30 +397,95 @@ RUN gem install jekyll -v '3.0.0.pre.beta9'
 ");
     }
 
+    #[test]
+    fn generate_code_for_jekyll_in_make_format_replays_environment_of_dependencies() {
+        let code = generate_code_for_jekyll_with_format("make");
+        assert!(code.contains("
+define ENVIRONMENT_update_0_0_0
+export DEBIAN_FRONTEND=noninteractive
+apt-get() { command apt-get --no-install-recommends \"$$@\"; }
+endef
+"), "{}", code);
+        assert!(code.contains("
+define ENVIRONMENT_wget_0_0_0
+$(ENVIRONMENT_update_0_0_0)
+endef
+"), "{}", code);
+        assert!(code.contains(" $(STAMP_DIR)/update-0.0.0
+\t$(ENVIRONMENT_update_0_0_0)
+\tapt-get install -q -y wget
+"), "{}", code);
+        assert!(code.contains(" $(STAMP_DIR)/ruby-2.2.3
+\t$(ENVIRONMENT_ruby_2_2_3)
+\tgem install jekyll -v '3.0.0.pre.beta9'
+"), "{}", code);
+    }
+
     #[test]
     fn generate_code_for_jekyll_in_shell_format() {
-        let code = generate_code_for_jekyll_with_format(Format::Shell);
-        assert_eq!(code, "export DEBIAN_FRONTEND=noninteractive
-apt-get update -q -y
-alias apt-get='apt-get --no-install-recommends'
-apt-get install -q -y apt-utils
-apt-get install -q -y wget
-cd /tmp
-wget https://cache.ruby-lang.org/pub/ruby/2.2/ruby-2.2.3.tar.gz
-tar xvzf ruby-2.2.3.tar.gz
-cd ruby-2.2.3
-./configure --prefix=/usr/local
-make
-make install
-cd ..
-rm -rf ruby-2.2.3
-wget https://rubygems.org/rubygems/rubygems-2.4.8.tgz
-tar xvzf rubygems-2.4.8.tgz
-cd rubygems-2.4.8
-ruby setup.rb
-cd ..
-rm -rf rubygems-2.4.8
-gem install jekyll -v '3.0.0.pre.beta9'
-");
+        let code = generate_code_for_jekyll_with_format("sh");
+        assert!(code.starts_with("#!/usr/bin/env bash\n"), "{}", code);
+        assert!(code.contains("\nSLICES='debian update wget ruby jekyll'\nTOTAL=5\n"), "{}", code);
+        assert!(code.ends_with("
+slice_debian_8_2_0() {
+    :
+}
+slice_debian_8_2_0_environment() {
+    :
+}
+
+slice_update_0_0_0() {
+    export DEBIAN_FRONTEND=noninteractive
+    apt-get update -q -y
+    apt-get() { command apt-get --no-install-recommends \"$@\"; }
+    apt-get install -q -y apt-utils
+}
+slice_update_0_0_0_environment() {
+    export DEBIAN_FRONTEND=noninteractive
+    apt-get() { command apt-get --no-install-recommends \"$@\"; }
+}
+
+slice_wget_0_0_0() {
+    apt-get install -q -y wget
+}
+slice_wget_0_0_0_environment() {
+    :
+}
+
+slice_ruby_2_2_3() {
+    cd /tmp
+    wget https://cache.ruby-lang.org/pub/ruby/2.2/ruby-2.2.3.tar.gz
+    tar xvzf ruby-2.2.3.tar.gz
+    cd ruby-2.2.3
+    ./configure --prefix=/usr/local
+    make
+    make install
+    cd ..
+    rm -rf ruby-2.2.3
+    wget https://rubygems.org/rubygems/rubygems-2.4.8.tgz
+    tar xvzf rubygems-2.4.8.tgz
+    cd rubygems-2.4.8
+    ruby setup.rb
+    cd ..
+    rm -rf rubygems-2.4.8
+}
+slice_ruby_2_2_3_environment() {
+    :
+}
+
+slice_jekyll_3_0_0_beta_9() {
+    gem install jekyll -v '3.0.0.pre.beta9'
+}
+slice_jekyll_3_0_0_beta_9_environment() {
+    :
+}
+
+run_slice 1 'debian' 'debian-8.2.0' slice_debian_8_2_0
+run_slice 2 'update' 'update-0.0.0' slice_update_0_0_0
+run_slice 3 'wget' 'wget-0.0.0' slice_wget_0_0_0
+run_slice 4 'ruby' 'ruby-2.2.3' slice_ruby_2_2_3
+run_slice 5 'jekyll' 'jekyll-3.0.0-beta.9' slice_jekyll_3_0_0_beta_9
+"),
+                code);
     }
 }
\ No newline at end of file
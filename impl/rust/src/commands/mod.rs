@@ -1,9 +1,15 @@
 pub use self::command::Command;
+pub use self::env_command::EnvCommand;
 pub use self::fetch_command::FetchCommand;
 pub use self::find_command::FindCommand;
+pub use self::list_command::ListCommand;
 pub use self::make_command::MakeCommand;
+pub use self::test_command::{BuildResult, TestCommand};
 
 mod command;
+mod env_command;
 mod find_command;
 mod fetch_command;
-mod make_command;
\ No newline at end of file
+mod list_command;
+mod make_command;
+mod test_command;
\ No newline at end of file
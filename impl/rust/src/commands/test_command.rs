@@ -0,0 +1,264 @@
+use std::io::{BufRead, BufReader, Write, stderr, stdout};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::Stdio;
+use std::thread;
+use RequestedSlice;
+use commands::command::Command;
+use commands::make_command::MakeCommand;
+use error::{Error, Result};
+use options_parse::Options;
+use os::Os;
+
+const IMAGE_TAG: &'static str = "sb-test";
+
+/// Outcome of a container engine run
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct BuildResult {
+    /// `None` if the builder was terminated by a signal
+    pub exit_code: Option<i32>,
+    /// Identifier of the built image if the builder reported it
+    pub image_id: Option<String>,
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+}
+
+impl BuildResult {
+    pub fn is_success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Generates a Dockerfile for the requested slices into `<root>/test/<slice>/Dockerfile`
+/// and builds it with the container engine from `Options::builder`
+pub struct TestCommand<'a> {
+    slices: Vec<RequestedSlice>,
+    os: Os,
+    root_directory: &'a Path,
+    slice_root_directory: &'a Path,
+    options: Options,
+}
+
+impl<'a> TestCommand<'a> {
+    /// # Panics
+    /// If `slices` is empty
+    pub fn new(slices: Vec<RequestedSlice>, os: Os, root_directory: &'a Path,
+               slice_root_directory: &'a Path, options: Options) -> TestCommand<'a> {
+        assert_not_empty!(slices);
+        TestCommand { slices: slices, os: os, root_directory: root_directory,
+                      slice_root_directory: slice_root_directory, options: options }
+    }
+
+    /// Writes the Dockerfile and runs the builder, its output is streamed to the console
+    /// # Errors
+    /// `Error::Io` if the builder cannot be started. A failed build is not an error here,
+    /// it is reported by `BuildResult::exit_code`.
+    pub fn build(&self) -> Result<BuildResult> {
+        let dockerfile_path = self.get_dockerfile_path();
+        let options = Options { format: String::from("d"),
+                                outpath: dockerfile_path.to_string_lossy().into_owned(),
+                                .. self.options.clone() };
+        let mut make_command = MakeCommand::new(self.slices.clone(), self.os.clone(),
+                                                self.root_directory, self.slice_root_directory,
+                                                options);
+        try!(make_command.run());
+        self.run_builder(&dockerfile_path)
+    }
+
+    fn get_dockerfile_path(&self) -> PathBuf {
+        let mut path = self.root_directory.to_path_buf();
+        path.push("test");
+        path.push(&self.slices.first().unwrap().name);
+        path.push("Dockerfile");
+        path
+    }
+
+    /// `buildah` names the subcommand `bud`, other engines follow the `docker build` syntax
+    fn get_builder_arguments(&self, dockerfile_path: &Path) -> Vec<String> {
+        let builder_name = Path::new(&self.options.builder).file_stem()
+                                                           .and_then(|n| n.to_str());
+        let subcommand = match builder_name {
+            Some("buildah") => "bud",
+            _ => "build",
+        };
+        let context_directory = dockerfile_path.parent().unwrap();
+        vec![subcommand.to_string(), String::from("-t"), IMAGE_TAG.to_string(),
+             String::from("-f"), dockerfile_path.to_string_lossy().into_owned(),
+             context_directory.to_string_lossy().into_owned()]
+    }
+
+    fn run_builder(&self, dockerfile_path: &Path) -> Result<BuildResult> {
+        let builder = &self.options.builder;
+        let mut child = try!(process::Command::new(builder)
+                                 .args(&self.get_builder_arguments(dockerfile_path))
+                                 .stdout(Stdio::piped())
+                                 .stderr(Stdio::piped())
+                                 .spawn()
+                                 .map_err(|e| Error::io(builder, e)));
+        let child_stderr = child.stderr.take().unwrap();
+        let stderr_thread = thread::spawn(move || {
+            forward_lines(BufReader::new(child_stderr), &mut stderr())
+        });
+        let stdout_lines = forward_lines(BufReader::new(child.stdout.take().unwrap()),
+                                         &mut stdout());
+        let stderr_lines = stderr_thread.join().unwrap_or(Vec::new());
+        let status = try!(child.wait().map_err(|e| Error::io(builder, e)));
+        let image_id = find_image_id(&stdout_lines).or_else(|| find_image_id(&stderr_lines));
+        Ok(BuildResult { exit_code: status.code(), image_id: image_id, stdout: stdout_lines,
+                         stderr: stderr_lines })
+    }
+}
+
+impl<'a> Command for TestCommand<'a> {
+    fn run(&mut self) -> Result<()> {
+        let result = try!(self.build());
+        if !result.is_success() {
+            let exit_code = result.exit_code.map_or(String::from("none"), |c| c.to_string());
+            return Err(Error::Build(format!("{} failed with exit code = {}",
+                                            self.options.builder, exit_code)));
+        }
+        match result.image_id {
+            Some(ref image_id) => println!("Test passed. Image = {}", image_id),
+            None => println!("Test passed"),
+        }
+        Ok(())
+    }
+}
+
+/// Copies lines from `reader` to `writer` as soon as they arrive and returns them
+fn forward_lines<R: BufRead, W: Write>(reader: R, writer: &mut W) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in reader.lines() {
+        match line {
+            Ok(line) => {
+                let _ = writeln!(writer, "{}", line);
+                lines.push(line);
+            }
+            Err(_) => break,
+        }
+    }
+    lines
+}
+
+/// Recognises the image id in the output of docker (`Successfully built <id>`), BuildKit
+/// (`writing image sha256:<id>`), podman and buildah (the id alone on the last line)
+fn find_image_id(lines: &[String]) -> Option<String> {
+    for line in lines.iter().rev() {
+        let line = line.trim();
+        if line.starts_with("Successfully built ") {
+            return line.split_whitespace().nth(2).map(str::to_string);
+        }
+        if let Some(index) = line.find("writing image sha256:") {
+            let id = &line[index + "writing image sha256:".len()..];
+            return id.split_whitespace().next().map(str::to_string);
+        }
+    }
+    lines.iter().rev().map(|l| l.trim()).find(|l| !l.is_empty()).and_then(|line| {
+        let is_id = line.len() >= 12 && line.chars().all(|c| c.is_digit(16));
+        if is_id { Some(line.to_string()) } else { None }
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use {Command, Error, RequestedSlice, TestCommand, VersionMatchStrategy};
+    use helper;
+    use options_parse::Options;
+    use os::Os;
+    use version;
+
+    fn create_builder(root_directory: &PathBuf, script: &str) -> String {
+        let mut path = root_directory.clone();
+        path.push("fake-builder");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(format!("#!/bin/sh\n{}\n", script).as_bytes()).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn create_command<'a>(root_directory: &'a PathBuf, slice_root_directory: &'a PathBuf,
+                          builder: String) -> TestCommand<'a> {
+        let slice = RequestedSlice { name: String::from("jekyll"),
+                                     version: version::parse("3.0.0-beta").unwrap(),
+                                     version_match_strategy: VersionMatchStrategy::ExactOrGreater };
+        let os = Os::parse("debian").unwrap();
+        let options = Options { builder: builder, .. Options::new() };
+        TestCommand::new(vec![slice], os, root_directory, slice_root_directory, options)
+    }
+
+    fn get_slice_root_directory() -> PathBuf {
+        let mut path = env::current_dir().expect("Current dir is not set").to_path_buf();
+        path.push("test_slices");
+        path
+    }
+
+    #[test]
+    fn build_passes_dockerfile_to_builder() {
+        let root_directory = helper::create_temporary_directory("sb-test").unwrap();
+        let slice_root_directory = get_slice_root_directory();
+        let builder = create_builder(&root_directory,
+                                     "echo \"$@\"\necho 'Successfully built 4e2f1b3c9a0d'");
+        let command = create_command(&root_directory, &slice_root_directory, builder);
+        let result = command.build().unwrap();
+
+        let mut dockerfile_path = root_directory.clone();
+        dockerfile_path.push("test/jekyll/Dockerfile");
+        let mut dockerfile = String::new();
+        File::open(&dockerfile_path).unwrap().read_to_string(&mut dockerfile).unwrap();
+        assert!(dockerfile.starts_with("FROM debian:jessie\n"));
+
+        assert!(result.is_success());
+        assert_eq!(result.image_id, Some(String::from("4e2f1b3c9a0d")));
+        assert_eq!(result.stdout[0], format!("build -t sb-test -f {} {}",
+                                             dockerfile_path.display(),
+                                             dockerfile_path.parent().unwrap().display()));
+        fs::remove_dir_all(&root_directory).unwrap();
+    }
+
+    #[test]
+    fn failed_build_is_an_error() {
+        let root_directory = helper::create_temporary_directory("sb-test").unwrap();
+        let slice_root_directory = get_slice_root_directory();
+        let builder = create_builder(&root_directory, "echo 'no space left' >&2\nexit 3");
+        let mut command = create_command(&root_directory, &slice_root_directory, builder);
+
+        let result = command.build().unwrap();
+        assert_eq!(result.exit_code, Some(3));
+        assert_eq!(result.stderr, vec![String::from("no space left")]);
+        match command.run() {
+            Err(Error::Build(_)) => {}
+            result => panic!("Build error expected, got {:?}", result),
+        }
+        fs::remove_dir_all(&root_directory).unwrap();
+    }
+
+    #[test]
+    fn missing_builder_is_an_io_error() {
+        let root_directory = helper::create_temporary_directory("sb-test").unwrap();
+        let slice_root_directory = get_slice_root_directory();
+        let builder = String::from("/path/that/does/not/exist/docker");
+        let command = create_command(&root_directory, &slice_root_directory, builder);
+        match command.build() {
+            Err(Error::Io(..)) => {}
+            result => panic!("I/O error expected, got {:?}", result),
+        }
+        fs::remove_dir_all(&root_directory).unwrap();
+    }
+
+    #[test]
+    fn image_id_of_podman_is_the_last_line() {
+        let lines = vec![String::from("COMMIT sb-test"),
+                         String::from("--> 8d7f0a1b2c3d"),
+                         String::from("8d7f0a1b2c3d4e5f")];
+        assert_eq!(super::find_image_id(&lines), Some(String::from("8d7f0a1b2c3d4e5f")));
+        assert_eq!(super::find_image_id(&[String::from("Error: no such file")]), None);
+    }
+}
@@ -0,0 +1,111 @@
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::result;
+
+/// Errors returned by every public API of the crate
+#[derive(Debug)]
+pub enum Error {
+    /// A file or a directory at the path cannot be accessed
+    Io(PathBuf, io::Error),
+    /// A version, a slice file or a server response cannot be understood
+    Parse(String),
+    /// Requested slices or their dependencies cannot be resolved
+    Resolution(String),
+    /// A remote resource cannot be downloaded
+    Network(String),
+    /// A downloaded archive cannot be read or extracted
+    Archive(String),
+    /// Command line arguments are invalid
+    Usage(String),
+    /// A container engine failed to build an image from generated code
+    Build(String),
+    /// A fetched archive does not match its checksum or signature
+    Verification(String),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    pub fn io<P: AsRef<Path>>(path: P, error: io::Error) -> Error {
+        Error::Io(path.as_ref().to_path_buf(), error)
+    }
+
+    /// Returns the process exit code which corresponds to the error
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Error::Usage(_) => 2,
+            Error::Io(..) => 3,
+            Error::Parse(_) => 4,
+            Error::Resolution(_) => 5,
+            Error::Network(_) => 6,
+            Error::Archive(_) => 7,
+            Error::Build(_) => 8,
+            Error::Verification(_) => 9,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match *self {
+            Error::Io(ref path, ref error) => {
+                write!(formatter, "I/O error at path = {}: {}", path.display(), error)
+            }
+            Error::Parse(ref message) => write!(formatter, "Parse error: {}", message),
+            Error::Resolution(ref message) => write!(formatter, "Resolution error: {}", message),
+            Error::Network(ref message) => write!(formatter, "Network error: {}", message),
+            Error::Archive(ref message) => write!(formatter, "Archive error: {}", message),
+            Error::Usage(ref message) => write!(formatter, "{}", message),
+            Error::Build(ref message) => write!(formatter, "Build error: {}", message),
+            Error::Verification(ref message) => {
+                write!(formatter, "Verification error: {}", message)
+            }
+        }
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(..) => "I/O error",
+            Error::Parse(_) => "parse error",
+            Error::Resolution(_) => "resolution error",
+            Error::Network(_) => "network error",
+            Error::Archive(_) => "archive error",
+            Error::Usage(_) => "usage error",
+            Error::Build(_) => "build error",
+            Error::Verification(_) => "verification error",
+        }
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            Error::Io(_, ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use super::Error;
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        let errors = vec![Error::Usage(String::new()),
+                          Error::io("/", io::Error::new(io::ErrorKind::Other, "")),
+                          Error::Parse(String::new()),
+                          Error::Resolution(String::new()),
+                          Error::Network(String::new()),
+                          Error::Archive(String::new()),
+                          Error::Build(String::new()),
+                          Error::Verification(String::new())];
+        let mut codes = errors.iter().map(Error::exit_code).collect::<Vec<_>>();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+    }
+}
@@ -1,17 +1,43 @@
-use slice::Slice;
+use formatters::Formatter;
+use plan::Step;
+use slice::{Kind, Section};
 
-pub fn generate_code(slice: &Slice) -> String {
+/// Dockerfile with one `RUN` instruction per `RUN` section, selected with `-f d`
+pub struct DockerFormatter;
+
+impl Formatter for DockerFormatter {
+    fn step(&self, step: &Step) -> String {
+        generate_step(step)
+    }
+}
+
+fn generate_step(step: &Step) -> String {
     let mut string = String::new();
-    if ! slice.ancestors().is_empty() {
-        for ancestor in slice.ancestors() {
-            string.push_str(&format!("FROM {}\n", ancestor));
+    for section in &step.sections {
+        match section.kind {
+            Kind::Os | Kind::Dep => {}
+            Kind::From => {
+                for ancestor in &section.items {
+                    string.push_str(&format!("FROM {}\n", ancestor));
+                }
+                string.push('\n');
+            }
+            Kind::Run => string.push_str(&generate_run(section)),
+            _ => {
+                for item in &section.items {
+                    string.push_str(&format!("{} {}\n", section.kind.keyword(), item));
+                }
+            }
         }
-        string.push('\n');
     }
+    string
+}
 
-    string.push_str("RUN ");
+/// Chains all commands of the section into a single `RUN` instruction to produce one layer
+fn generate_run(section: &Section) -> String {
+    let mut string = String::from("RUN ");
     let mut is_first = true;
-    for preparation in slice.preparations() {
+    for preparation in &section.items {
         if is_first {
             is_first = false;
         } else {
@@ -25,40 +51,78 @@ pub fn generate_code(slice: &Slice) -> String {
 
 #[cfg(test)]
 mod tests {
-    use slice::Slice;
+    use std::path::PathBuf;
+    use plan::{Reason, Step};
+    use slice::{Kind, Section};
     use version;
 
-    fn create_slice(ancestors: Vec<&str>, preparations: Vec<&str>) -> Slice {
-        let ancestors = ancestors.into_iter().map(str::to_string).collect();
-        let preparations = preparations.into_iter().map(str::to_string).collect();
-        let name = String::from("Hello");
-        Slice::new(name, version::zero(), ancestors, preparations, Vec::new(), Vec::new())
+    fn create_step(ancestors: Vec<&str>, preparations: Vec<&str>) -> Step {
+        let mut sections = Vec::new();
+        if !ancestors.is_empty() {
+            sections.push(create_section(Kind::From, ancestors));
+        }
+        sections.push(create_section(Kind::Run, preparations));
+        create_step_with_sections(sections)
+    }
+
+    fn create_step_with_sections(sections: Vec<Section>) -> Step {
+        Step { name: String::from("Hello"), version: version::zero(),
+               bunch: String::from("slices-0.0.0"), path: PathBuf::new(),
+               reason: Reason::Requested, dependencies: Vec::new(), sections: sections }
+    }
+
+    fn create_section(kind: Kind, items: Vec<&str>) -> Section {
+        Section::new(kind, items.into_iter().map(str::to_string).collect())
     }
 
     #[test]
     fn generate_code_for_slice_with_one_preparation() {
-        let slice = create_slice(Vec::new(), vec!["apt-get install -q -y wget"]);
-        assert_eq!(super::generate_code(&slice), "RUN apt-get install -q -y wget\n");
+        let step = create_step(Vec::new(), vec!["apt-get install -q -y wget"]);
+        assert_eq!(super::generate_step(&step), "RUN apt-get install -q -y wget\n");
     }
 
     #[test]
     fn generate_code_for_slice_with_one_ancestor_and_one_preparation() {
-        let slice = create_slice(vec!["base"], vec!["apt-get install -q -y wget"]);
-        assert_eq!(super::generate_code(&slice), "FROM base
+        let step = create_step(vec!["base"], vec!["apt-get install -q -y wget"]);
+        assert_eq!(super::generate_step(&step), "FROM base
 
 RUN apt-get install -q -y wget\n");
     }
 
     #[test]
     fn generate_code_for_slice_with_two_ancestors_and_two_preparations() {
-        let slice = create_slice(vec!["base", "another_base"],
+        let step = create_step(vec!["base", "another_base"],
                                  vec!["apt-get install -q -y wget",
                                       "apt-get install -q -y wget_gui"]);
-        assert_eq!(super::generate_code(&slice), "FROM base
+        assert_eq!(super::generate_step(&step), "FROM base
 FROM another_base
 
 RUN apt-get install -q -y wget && \\
 apt-get install -q -y wget_gui
+");
+    }
+
+    #[test]
+    fn generate_code_keeps_order_of_all_sections() {
+        let step = create_step_with_sections(vec![
+            create_section(Kind::Env, vec!["NGINX_VERSION=1.9.9"]),
+            create_section(Kind::Run, vec!["apt-get install -q -y nginx"]),
+            create_section(Kind::WorkDir, vec!["/var/www"]),
+            create_section(Kind::Copy, vec!["site /var/www"]),
+            create_section(Kind::User, vec!["www-data"]),
+            create_section(Kind::Run, vec!["touch index.html"]),
+            create_section(Kind::Expose, vec!["80 443"]),
+            create_section(Kind::Volume, vec!["/var/log/nginx"]),
+            create_section(Kind::Cmd, vec!["[\"nginx\", \"-g\", \"daemon off;\"]"])]);
+        assert_eq!(super::generate_step(&step), "ENV NGINX_VERSION=1.9.9
+RUN apt-get install -q -y nginx
+WORKDIR /var/www
+COPY site /var/www
+USER www-data
+RUN touch index.html
+EXPOSE 80 443
+VOLUME /var/log/nginx
+CMD [\"nginx\", \"-g\", \"daemon off;\"]
 ");
     }
 }
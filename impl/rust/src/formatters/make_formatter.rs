@@ -0,0 +1,164 @@
+use formatters::Formatter;
+use formatters::shell_formatter;
+use plan::{Plan, Reason, Step};
+
+/// GNU Makefile with one target per slice, selected with `-f make`
+///
+/// `DEP` entries become prerequisites, so `make -j` installs independent slices in parallel.
+/// A slice is installed by its stamp file target in `STAMP_DIR`, which is remade when the
+/// stamp is missing or older than the slice file or the stamps of its dependencies. Every
+/// recipe runs in its own strict-mode shell, so the `ENV`, `export` and `alias` commands of a
+/// slice are also kept in its `ENVIRONMENT_<slice>` variable together with the environment of
+/// its dependencies. Recipes start with the environment of the dependencies.
+pub struct MakeFormatter;
+
+impl Formatter for MakeFormatter {
+    fn header(&self, plan: &Plan) -> String {
+        let names = plan.steps().iter().map(|step| step.name.as_str()).collect::<Vec<_>>();
+        let requested_names = plan.requested_steps()
+                                  .iter()
+                                  .map(|step| step.name.as_str())
+                                  .collect::<Vec<_>>();
+        let os_stamp = plan.steps()
+                           .iter()
+                           .find(|step| step.reason == Reason::Os)
+                           .map(stamp)
+                           .unwrap_or_default();
+        format!("# Generated by sb. Run `make -j` to install independent slices in parallel.
+SHELL := /bin/bash
+.SHELLFLAGS := -euo pipefail -c
+.ONESHELL:
+
+STAMP_DIR ?= $(CURDIR)/.sb-stamps
+OS_STAMP := {}
+
+.PHONY: all {}
+all: {}
+
+",
+                os_stamp, names.join(" "), requested_names.join(" "))
+    }
+
+    fn step(&self, step: &Step) -> String {
+        let mut prerequisites = vec![escape_path(&step.path.display().to_string())];
+        if step.reason != Reason::Os {
+            prerequisites.push(String::from("$(OS_STAMP)"));
+        }
+        prerequisites.extend(step.dependencies.iter().map(|name| stamp_of(name)));
+        let references = step.dependencies
+                             .iter()
+                             .map(|name| format!("$({})", environment_variable(name)))
+                             .collect::<Vec<_>>();
+        let environment = shell_formatter::generate_environment(step);
+        let mut string = String::new();
+        if !references.is_empty() || !environment.is_empty() {
+            string.push_str(&format!("define {}\n", environment_variable(&step.full_name())));
+            for reference in &references {
+                string.push_str(&format!("{}\n", reference));
+            }
+            for line in environment.lines() {
+                string.push_str(&format!("{}\n", line.replace("$", "$$")));
+            }
+            string.push_str("endef\n");
+        }
+        string.push_str(&format!("{}: {}\n{}: {}\n", step.name, stamp(step), stamp(step),
+                                 prerequisites.join(" ")));
+        for reference in &references {
+            string.push_str(&format!("\t{}\n", reference));
+        }
+        for line in shell_formatter::generate_commands(step).lines() {
+            string.push_str(&format!("\t{}\n", line.replace("$", "$$")));
+        }
+        string.push_str("\tmkdir -p '$(@D)'\n\ttouch '$@'\n");
+        string
+    }
+
+    fn separator(&self, _previous: &Step, _next: &Step) -> String {
+        String::from("\n")
+    }
+}
+
+fn stamp(step: &Step) -> String {
+    stamp_of(&step.full_name())
+}
+
+/// Stamp file of the slice with the full name, e.g. `$(STAMP_DIR)/ruby-2.2.3`
+fn stamp_of(full_name: &str) -> String {
+    format!("$(STAMP_DIR)/{}", escape_path(full_name))
+}
+
+/// Variable with the environment of the slice with the full name, e.g. `ENVIRONMENT_ruby_2_2_3`
+fn environment_variable(full_name: &str) -> String {
+    let name = full_name.chars()
+                        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                        .collect::<String>();
+    format!("ENVIRONMENT_{}", name)
+}
+
+/// Escapes characters which make treats specially in targets and prerequisites
+fn escape_path(path: &str) -> String {
+    path.replace("$", "$$").replace(" ", "\\ ").replace(":", "\\:").replace("#", "\\#")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use formatters::Formatter;
+    use plan::{Plan, Reason, Step};
+    use slice::{Kind, Section};
+    use version;
+    use super::MakeFormatter;
+
+    fn create_step(name: &str, reason: Reason, dependencies: &[&str], commands: &[&str]) -> Step {
+        let commands = commands.iter().map(|command| command.to_string()).collect();
+        Step { name: String::from(name), version: version::parse("1.0.0").unwrap(),
+               bunch: String::from("slices-0.0.0"), path: PathBuf::from(format!("/s/{}", name)),
+               reason: reason,
+               dependencies: dependencies.iter().map(|name| name.to_string()).collect(),
+               sections: vec![Section::new(Kind::Run, commands)] }
+    }
+
+    #[test]
+    fn generate_target_per_slice_with_dependencies_as_prerequisites() {
+        let plan = Plan::from_steps(vec![create_step("debian", Reason::Os, &[], &[]),
+                                         create_step("wget", Reason::Dependency, &[],
+                                                     &["apt-get install -q -y wget"]),
+                                         create_step("ruby", Reason::Requested,
+                                                     &["wget-1.0.0"],
+                                                     &["cd /tmp", "echo $HOME"])]);
+        assert_eq!(MakeFormatter.generate_code(&plan), "# Generated by sb. Run `make -j` to \
+                                                        install independent slices in parallel.
+SHELL := /bin/bash
+.SHELLFLAGS := -euo pipefail -c
+.ONESHELL:
+
+STAMP_DIR ?= $(CURDIR)/.sb-stamps
+OS_STAMP := $(STAMP_DIR)/debian-1.0.0
+
+.PHONY: all debian wget ruby
+all: ruby
+
+debian: $(STAMP_DIR)/debian-1.0.0
+$(STAMP_DIR)/debian-1.0.0: /s/debian
+\tmkdir -p '$(@D)'
+\ttouch '$@'
+
+wget: $(STAMP_DIR)/wget-1.0.0
+$(STAMP_DIR)/wget-1.0.0: /s/wget $(OS_STAMP)
+\tapt-get install -q -y wget
+\tmkdir -p '$(@D)'
+\ttouch '$@'
+
+define ENVIRONMENT_ruby_1_0_0
+$(ENVIRONMENT_wget_1_0_0)
+endef
+ruby: $(STAMP_DIR)/ruby-1.0.0
+$(STAMP_DIR)/ruby-1.0.0: /s/ruby $(OS_STAMP) $(STAMP_DIR)/wget-1.0.0
+\t$(ENVIRONMENT_wget_1_0_0)
+\tcd /tmp
+\techo $$HOME
+\tmkdir -p '$(@D)'
+\ttouch '$@'
+");
+    }
+}
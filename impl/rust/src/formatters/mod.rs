@@ -1,16 +1,170 @@
-use slice::Slice;
-use options_parse::Format;
+//! Formatters which generate code from a plan
+//!
+//! Formatters are selected by name with `-f <name>`, or with `-f <name>:<argument>` if they
+//! are created from an argument like `template:PATH`. The built-in `d`, `sh`, `make` and
+//! `template` are always registered, library users can register their own with
+//! `Registry::register`.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use error::{Error, Result};
+use plan::{Plan, Step};
+
+pub use self::docker_formatter::DockerFormatter;
+pub use self::make_formatter::MakeFormatter;
+pub use self::shell_formatter::ShellFormatter;
+pub use self::template_formatter::TemplateFormatter;
 
 mod docker_formatter;
+mod make_formatter;
 mod shell_formatter;
+mod template_formatter;
+
+/// Generates code for a whole plan. Only `step` is required, the other hooks generate nothing
+/// by default.
+pub trait Formatter {
+    /// Code before the first step, e.g. a shebang
+    fn header(&self, _plan: &Plan) -> String {
+        String::new()
+    }
+
+    /// Code which installs a single slice
+    fn step(&self, step: &Step) -> String;
+
+    /// Code between two consecutive steps
+    fn separator(&self, _previous: &Step, _next: &Step) -> String {
+        String::new()
+    }
+
+    /// Code after the last step
+    fn footer(&self, _plan: &Plan) -> String {
+        String::new()
+    }
+
+    /// Generates code for every step of `plan` in its order, surrounded by the header and
+    /// the footer
+    fn generate_code(&self, plan: &Plan) -> String {
+        let mut code = self.header(plan);
+        let mut previous: Option<&Step> = None;
+        for step in plan.steps() {
+            if let Some(previous) = previous {
+                code.push_str(&self.separator(previous, step));
+            }
+            code.push_str(&self.step(step));
+            previous = Some(step);
+        }
+        code.push_str(&self.footer(plan));
+        code
+    }
+}
+
+/// Creates a formatter from the argument of `-f <name>:<argument>`
+pub type Factory = fn(&str) -> Result<Box<Formatter>>;
+
+/// Formatters and factories of formatters by name
+pub struct Registry {
+    formatters: BTreeMap<String, Rc<Formatter>>,
+    factories: BTreeMap<String, Factory>,
+}
+
+impl Registry {
+    /// Creates a registry with the built-in formatters `d`, `sh`, `make` and `template`
+    pub fn new() -> Registry {
+        let mut registry = Registry { formatters: BTreeMap::new(), factories: BTreeMap::new() };
+        registry.register("d", Box::new(DockerFormatter));
+        registry.register("sh", Box::new(ShellFormatter));
+        registry.register("make", Box::new(MakeFormatter));
+        registry.register_factory("template", template_formatter::create);
+        registry
+    }
+
+    /// Registers `formatter` under `name`, a formatter already registered under it is replaced
+    pub fn register(&mut self, name: &str, formatter: Box<Formatter>) {
+        self.formatters.insert(name.to_string(), Rc::from(formatter));
+    }
+
+    /// Registers `factory` under `name`, it is called for every `-f <name>:<argument>`
+    pub fn register_factory(&mut self, name: &str, factory: Factory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    /// Names of the registered formatters in alphabetical order, factories as
+    /// `<name>:<argument>`
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self.formatters.keys().cloned().collect::<Vec<_>>();
+        names.extend(self.factories.keys().map(|name| format!("{}:<argument>", name)));
+        names.sort();
+        names
+    }
+
+    /// Returns the formatter registered under `format`, or creates it if `format` is
+    /// `<name>:<argument>`
+    /// # Errors
+    /// * `Error::Usage` if no formatter or factory is registered under the name
+    /// * an error of the factory, e.g. `Error::Parse` for an invalid template
+    pub fn find(&self, format: &str) -> Result<Rc<Formatter>> {
+        let formatter = match format.find(':') {
+            Some(index) => {
+                self.factories.get(&format[..index]).map(|factory| {
+                    factory(&format[index + 1..]).map(Rc::from)
+                })
+            }
+            None => self.formatters.get(format).map(|formatter| Ok(formatter.clone())),
+        };
+        formatter.unwrap_or_else(|| {
+            Err(Error::Usage(format!("Unknown format = {}. Available formats = [{}]", format,
+                                     self.names().join(", "))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use error::Error;
+    use plan::{Plan, Reason, Step};
+    use version;
+    use super::{Formatter, Registry};
+
+    struct NameFormatter;
+
+    impl Formatter for NameFormatter {
+        fn header(&self, _plan: &Plan) -> String {
+            String::from("[")
+        }
+
+        fn step(&self, step: &Step) -> String {
+            step.name.clone()
+        }
+
+        fn separator(&self, _previous: &Step, _next: &Step) -> String {
+            String::from(", ")
+        }
+
+        fn footer(&self, _plan: &Plan) -> String {
+            String::from("]")
+        }
+    }
+
+    fn create_step(name: &str) -> Step {
+        Step { name: String::from(name), version: version::zero(),
+               bunch: String::from("slices-0.0.0"), path: PathBuf::from(name),
+               reason: Reason::Requested, dependencies: Vec::new(), sections: Vec::new() }
+    }
 
-/// Generates code for sections
-/// # Arguments
-/// * `slice` slice
-/// * `format` code format
-pub fn generate_code(slice: &Slice, format: &Format) -> String {
-    match *format {
-		Format::Docker => docker_formatter::generate_code(slice),
-		Format::Shell => shell_formatter::generate_code(slice),
-	}
-}
\ No newline at end of file
+    #[test]
+    fn registered_formatter_is_found_by_name() {
+        let mut registry = Registry::new();
+        registry.register("names", Box::new(NameFormatter));
+        assert_eq!(registry.names(), vec!["d", "make", "names", "sh", "template:<argument>"]);
+        let steps = vec![create_step("debian"), create_step("update"), create_step("wget")];
+        let plan = Plan::from_steps(steps);
+        assert_eq!(registry.find("names").unwrap().generate_code(&plan), "[debian, update, wget]");
+        match registry.find("xml") {
+            Err(Error::Usage(ref message)) => assert!(message.contains("[d, make, names, ")),
+            _ => panic!("Usage error expected"),
+        }
+        assert!(match registry.find("template:/nonexistent") { Err(Error::Io(..)) => true,
+                                                                _ => false });
+    }
+}
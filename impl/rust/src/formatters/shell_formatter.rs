@@ -1,38 +1,342 @@
-use slice::Slice;
+use formatters::Formatter;
+use plan::{Plan, Step};
+use slice::Kind;
 
-pub fn generate_code(slice: &Slice) -> String {
-    let mut string = String::new();
-    for preparation in slice.preparations() {
-        string.push_str(preparation);
-        string.push('\n');
+/// Parses the arguments of the script and defines `run_slice`, which runs the function of a
+/// slice unless the slice is completed or not selected. Only the environment function of a
+/// skipped slice is run. `SLICES` and `TOTAL` are defined before it.
+const PREAMBLE: &'static str = r#"only=""
+from=""
+while [ $# -gt 0 ]; do
+    case "$1" in
+        --only|--from)
+            if [ $# -lt 2 ] || ! printf '%s\n' $SLICES | grep -qxF -- "$2"; then
+                echo "$1 expects one of: $SLICES" >&2
+                exit 2
+            fi
+            if [ "$1" = --only ]; then only=$2; else from=$2; fi
+            shift 2
+            ;;
+        *)
+            echo "Usage: $0 [--only SLICE | --from SLICE]" >&2
+            exit 2
+            ;;
+    esac
+done
+
+state_file=${SB_STATE_FILE:-$(cd "$(dirname "$0")" && pwd)/$(basename "$0").state}
+touch "$state_file"
+from_is_reached=""
+
+run_slice() {
+    local number=$1 name=$2 full_name=$3 function=$4 is_skipped=""
+    if [ -n "$only" ]; then
+        [ "$only" = "$name" ] || is_skipped=1
+    elif [ -n "$from" ]; then
+        [ "$from" = "$name" ] && from_is_reached=1
+        [ -n "$from_is_reached" ] || is_skipped=1
+    elif grep -qxF -- "$full_name" "$state_file"; then
+        echo "==> [$number/$TOTAL] $full_name is completed, skipping"
+        is_skipped=1
+    fi
+    if [ -n "$is_skipped" ]; then
+        "${function}_environment"
+        return 0
+    fi
+    echo "==> [$number/$TOTAL] Installing $full_name"
+    "$function"
+    grep -qxF -- "$full_name" "$state_file" || echo "$full_name" >> "$state_file"
+    echo "==> [$number/$TOTAL] Completed $full_name"
+}
+"#;
+
+/// Bash script with one function per slice, selected with `-f sh`
+///
+/// The script runs in strict mode and records every completed slice in a state file,
+/// `<script>.state` unless `SB_STATE_FILE` is set. Completed slices are skipped when the
+/// script is run again. `--only SLICE` runs a single slice, `--from SLICE` runs a slice and
+/// the slices after it, both even if the slices are completed.
+///
+/// Every slice also has an environment function with its `ENV`, `export` and `alias` commands,
+/// which is run instead of the slice function when the slice is skipped, so the following
+/// slices see the same environment.
+pub struct ShellFormatter;
+
+impl Formatter for ShellFormatter {
+    fn header(&self, plan: &Plan) -> String {
+        let names = plan.steps().iter().map(|step| step.name.as_str()).collect::<Vec<_>>();
+        format!("#!/usr/bin/env bash
+# Generated by sb. Usage: <script> [--only SLICE | --from SLICE]
+set -euo pipefail
+
+SLICES={}
+TOTAL={}
+{}
+",
+                quote(&names.join(" ")), names.len(), PREAMBLE)
+    }
+
+    fn step(&self, step: &Step) -> String {
+        let name = function_name(step);
+        let mut string = function(&name, &generate_commands(step));
+        string.push_str(&function(&format!("{}_environment", name),
+                                  &generate_environment(step)));
+        string
+    }
+
+    fn separator(&self, _previous: &Step, _next: &Step) -> String {
+        String::from("\n")
+    }
+
+    fn footer(&self, plan: &Plan) -> String {
+        let mut string = String::from("\n");
+        for (index, step) in plan.steps().iter().enumerate() {
+            string.push_str(&format!("run_slice {} {} {} {}\n", index + 1, quote(&step.name),
+                                     quote(&step.full_name()), function_name(step)));
+        }
+        string
+    }
+}
+
+/// Defines a function with `commands` as its body, `:` if there are no commands
+fn function(name: &str, commands: &str) -> String {
+    let mut string = format!("{}() {{\n", name);
+    if commands.is_empty() {
+        string.push_str("    :\n");
     }
+    for line in commands.lines() {
+        string.push_str(&format!("    {}\n", line));
+    }
+    string.push_str("}\n");
     string
 }
 
+/// Name of the function which installs the slice, e.g. `slice_ruby_2_2_3`
+fn function_name(step: &Step) -> String {
+    let name = step.full_name()
+                   .chars()
+                   .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                   .collect::<String>();
+    format!("slice_{}", name)
+}
+
+/// Generates shell commands for a step
+///
+/// Instructions which have no shell counterpart, e.g. `EXPOSE` or `CMD`, are kept as comments.
+/// `USER` applies to the following `RUN` commands of the same slice only. Aliases are not
+/// expanded in functions and non-interactive shells, so `alias` commands are defined as
+/// functions.
+pub fn generate_commands(step: &Step) -> String {
+    join(translate_sections(step).into_iter().map(|(command, _)| command))
+}
+
+/// Generates the commands of a step which change the environment of the following steps:
+/// `ENV`, and `export` and `alias` commands of `RUN`, the latter as functions
+pub fn generate_environment(step: &Step) -> String {
+    let commands = translate_sections(step).into_iter()
+                                           .filter(|&(_, changes_environment)| {
+                                               changes_environment
+                                           })
+                                           .map(|(command, _)| command);
+    join(commands)
+}
+
+/// Translates the sections of a step to shell commands
+/// # Returns
+/// The commands and whether they change the environment of the following steps
+fn translate_sections(step: &Step) -> Vec<(String, bool)> {
+    let mut commands = Vec::new();
+    let mut user: Option<&String> = None;
+    for section in &step.sections {
+        for item in &section.items {
+            match section.kind {
+                Kind::Os | Kind::Dep | Kind::From => {}
+                Kind::Run => {
+                    match user {
+                        Some(user) => {
+                            commands.push((format!("su {} -s /bin/sh -c {}", user, quote(item)),
+                                           false))
+                        }
+                        None => {
+                            let first_word = item.split_whitespace().next().unwrap_or("");
+                            match first_word {
+                                "export" => commands.push((item.clone(), true)),
+                                "alias" => {
+                                    let command = translate_alias(item)
+                                                      .unwrap_or_else(|| item.clone());
+                                    commands.push((command, true))
+                                }
+                                _ => commands.push((item.clone(), false)),
+                            }
+                        }
+                    }
+                }
+                Kind::Env => commands.push((format!("export {}", translate_env(item)), true)),
+                Kind::WorkDir => {
+                    let directory = quote(item);
+                    commands.push((format!("mkdir -p {} && cd {}", directory, directory), false));
+                }
+                Kind::User => user = Some(item),
+                _ => commands.push((format!("# {} {}", section.kind.keyword(), item), false)),
+            }
+        }
+    }
+    commands
+}
+
+fn join<I: Iterator<Item=String>>(commands: I) -> String {
+    commands.map(|command| format!("{}\n", command)).collect()
+}
+
+/// Converts `alias name='value'` to a function, e.g. `alias apt-get='apt-get -q'` to
+/// `apt-get() { command apt-get -q "$@"; }`
+/// # Returns
+/// `None` if the command does not define exactly one alias
+fn translate_alias(item: &str) -> Option<String> {
+    let definition = item.trim()["alias".len()..].trim();
+    let (name, value) = match definition.find('=') {
+        Some(index) => (&definition[..index], &definition[index + 1..]),
+        None => return None,
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) || name.starts_with('-') {
+        return None;
+    }
+    let value = match value.chars().next() {
+        Some(quote) if quote == '\'' || quote == '"' => {
+            if value.len() < 2 || !value.ends_with(quote) ||
+               value[1..value.len() - 1].contains(quote) {
+                return None;
+            }
+            &value[1..value.len() - 1]
+        }
+        _ if value.contains(char::is_whitespace) => return None,
+        _ => value,
+    };
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    let command = if value.split_whitespace().next() == Some(name) { "command " } else { "" };
+    Some(format!("{}() {{ {}{} \"$@\"; }}", name, command, value))
+}
+
+/// Converts both `KEY=value` and the legacy `KEY value` forms of `ENV`
+fn translate_env(item: &str) -> String {
+    let item = item.trim();
+    let separator = item.find(|c: char| c == '=' || c.is_whitespace());
+    match separator {
+        Some(index) if !item[index..].starts_with('=') => {
+            let (key, value) = item.split_at(index);
+            format!("{}={}", key, quote(value.trim()))
+        }
+        _ => item.to_string(),
+    }
+}
+
+/// Wraps a string into single quotes for the shell
+fn quote(string: &str) -> String {
+    format!("'{}'", string.replace("'", "'\\''"))
+}
+
 #[cfg(test)]
 mod tests {
-    use slice::Slice;
+    use std::fs;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+    use std::process::Command;
+    use formatters::Formatter;
+    use helper;
+    use plan::{Plan, Reason, Step};
+    use slice::{Kind, Section};
     use version;
+    use super::ShellFormatter;
+
+    fn create_step(ancestors: Vec<&str>, preparations: Vec<&str>) -> Step {
+        create_step_with_sections(vec![create_section(Kind::From, ancestors),
+                                        create_section(Kind::Run, preparations)])
+    }
+
+    fn create_step_with_sections(sections: Vec<Section>) -> Step {
+        Step { name: String::from("slice"), version: version::zero(),
+               bunch: String::from("slices-0.0.0"), path: PathBuf::new(),
+               reason: Reason::Requested, dependencies: Vec::new(), sections: sections }
+    }
 
-    fn create_slice(ancestors: Vec<&str>, preparations: Vec<&str>) -> Slice {
-        let ancestors = ancestors.into_iter().map(str::to_string).collect();
-        let preparations = preparations.into_iter().map(str::to_string).collect();
-        let name = String::from("slice");
-        Slice::new(name, version::zero(), ancestors, preparations, Vec::new(), Vec::new())
+    fn create_section(kind: Kind, items: Vec<&str>) -> Section {
+        Section::new(kind, items.into_iter().map(str::to_string).collect())
     }
 
     #[test]
     fn generate_code_for_slice_with_one_ancestor_and_one_preparation() {
-        let slice = create_slice(vec!["base"], vec!["apt-get install -q -y wget"]);
-        assert_eq!(super::generate_code(&slice), "apt-get install -q -y wget\n");
+        let step = create_step(vec!["base"], vec!["apt-get install -q -y wget"]);
+        assert_eq!(super::generate_commands(&step), "apt-get install -q -y wget\n");
     }
 
     #[test]
     fn generate_code_for_slice_with_one_ancestor_and_two_preparations() {
-        let slice = create_slice(vec!["base"], vec!["apt-get install -q -y wget",
+        let step = create_step(vec!["base"], vec!["apt-get install -q -y wget",
                                                     "apt-get install -q -y wget_gui"]);
-        assert_eq!(super::generate_code(&slice), "apt-get install -q -y wget
+        assert_eq!(super::generate_commands(&step), "apt-get install -q -y wget
 apt-get install -q -y wget_gui
 ");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn generate_code_translates_instructions() {
+        let step = create_step_with_sections(vec![
+            create_section(Kind::Env, vec!["NGINX_VERSION=1.9.9", "GREETING Hello, world"]),
+            create_section(Kind::WorkDir, vec!["/var/www"]),
+            create_section(Kind::Run, vec!["apt-get install -q -y nginx"]),
+            create_section(Kind::User, vec!["www-data"]),
+            create_section(Kind::Run, vec!["echo 'It works' > index.html"]),
+            create_section(Kind::Expose, vec!["80 443"])]);
+        assert_eq!(super::generate_commands(&step), "export NGINX_VERSION=1.9.9
+export GREETING='Hello, world'
+mkdir -p '/var/www' && cd '/var/www'
+apt-get install -q -y nginx
+su www-data -s /bin/sh -c 'echo '\\''It works'\\'' > index.html'
+# EXPOSE 80 443
+");
+    }
+
+    #[test]
+    fn generate_code_translates_alias_to_function() {
+        let step = create_step(vec![], vec!["alias apt-get='apt-get --no-install-recommends'",
+                                            "alias ll=\"ls -l\"", "alias a=b c=d"]);
+        assert_eq!(super::generate_commands(&step),
+                   "apt-get() { command apt-get --no-install-recommends \"$@\"; }
+ll() { ls -l \"$@\"; }
+alias a=b c=d
+");
+    }
+
+    #[test]
+    fn skipped_slice_sets_environment_of_following_slices() {
+        let directory = helper::create_temporary_directory("sb-shell").unwrap();
+        let run = |name: &str, commands: Vec<&str>| {
+            Step { name: String::from(name),
+                   .. create_step_with_sections(vec![create_section(Kind::Run, commands)]) }
+        };
+        let plan = Plan::from_steps(vec![
+            run("a", vec!["export GREETING=hello", "alias greet='echo'"]),
+            run("b", vec!["greet \"$GREETING\" >> \"$OUTPUT\""])]);
+        let script = directory.join("install.sh");
+        let code = ShellFormatter.generate_code(&plan);
+        File::create(&script).unwrap().write_all(code.as_bytes()).unwrap();
+        let output = directory.join("output");
+        for arguments in &[vec![], vec!["--only", "b"], vec!["--from", "b"], vec![]] {
+            let status = Command::new("bash").arg(&script)
+                                             .args(arguments)
+                                             .env("OUTPUT", &output)
+                                             .env_remove("SB_STATE_FILE")
+                                             .status()
+                                             .unwrap();
+            assert!(status.success(), "{:?} failed with {}", arguments, status);
+        }
+        let mut content = String::new();
+        File::open(&output).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "hello\nhello\nhello\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,601 @@
+//! Formatter which renders a user-supplied template, selected with `-f template:PATH`
+//!
+//! ```text
+//! #!/bin/sh
+//! # Generated for {{ os.full_name }}
+//! {% for step in requested %}
+//! echo "Installing {{ step.name }} {{ step.version }} from {{ step.bunch }}"
+//! {% endfor %}
+//! {% for step in steps %}
+//! {% for section in step.sections %}
+//! {% if section.kind == "RUN" %}
+//! {% for item in section.items %}
+//! {{ item }}
+//! {% endfor %}
+//! {% endif %}
+//! {% endfor %}
+//! {% endfor %}
+//! ```
+//!
+//! Variables:
+//!
+//! * `os` is the OS step, `steps` all steps in the install order, `requested` the steps of the
+//!   requested slices
+//! * a step has `name`, `version`, `full_name`, `bunch`, `path`, `reason` (`os`, `requested`
+//!   or `dependency`), `dependencies`, the full names of the steps it depends on, and
+//!   `sections`
+//! * a section has `kind`, e.g. `RUN`, and `items`
+//! * `loop` inside a `for` has `index` starting at 1, `first` and `last`
+//!
+//! `if` takes a variable, `not` and a variable, or a variable compared with a quoted string by
+//! `==` or `!=`, and may have an `else`. A line which holds nothing but a `{% %}` tag is not
+//! copied to the output. Variables are checked when the template is parsed.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::result;
+use error::{Error, Result};
+use formatters::Formatter;
+use plan::{Plan, Reason, Step};
+use slice::Section;
+
+pub struct TemplateFormatter {
+    nodes: Vec<Node>,
+}
+
+impl TemplateFormatter {
+    /// # Errors
+    /// `Error::Parse` with the line number if the template has a syntax error or an unknown
+    /// variable
+    pub fn parse(source: &str) -> Result<TemplateFormatter> {
+        let tokens = try!(tokenize(source).map_err(template_error));
+        let mut parser = Parser { tokens: &tokens, position: 0, scope: initial_types() };
+        let nodes = try!(parser.parse_nodes(&[], None, 0).map_err(template_error)).0;
+        Ok(TemplateFormatter { nodes: nodes })
+    }
+
+    /// # Errors
+    /// * `Error::Io` if the file cannot be read
+    /// * `Error::Parse` if the file is not a valid template
+    pub fn load(path: &Path) -> Result<TemplateFormatter> {
+        let mut file = try!(File::open(path).map_err(|e| Error::io(path, e)));
+        let mut source = String::new();
+        try!(file.read_to_string(&mut source).map_err(|e| Error::io(path, e)));
+        TemplateFormatter::parse(&source).map_err(|error| {
+            match error {
+                Error::Parse(message) => {
+                    Error::Parse(format!("{}. Path = {}", message, path.display()))
+                }
+                error => error,
+            }
+        })
+    }
+}
+
+impl Formatter for TemplateFormatter {
+    /// Renders the template for a plan of the single step
+    fn step(&self, step: &Step) -> String {
+        self.generate_code(&Plan::from_steps(vec![step.clone()]))
+    }
+
+    fn generate_code(&self, plan: &Plan) -> String {
+        let mut scope = initial_values(plan);
+        let mut code = String::new();
+        render(&self.nodes, &mut scope, &mut code);
+        code
+    }
+}
+
+/// Creates the formatter for `-f template:PATH`
+pub fn create(path: &str) -> Result<Box<Formatter>> {
+    Ok(Box::new(try!(TemplateFormatter::load(Path::new(path)))))
+}
+
+/// Line number and description of a template error
+type TemplateError = (usize, String);
+
+type ParseResult<T> = result::Result<T, TemplateError>;
+
+fn template_error((line, message): TemplateError) -> Error {
+    Error::Parse(format!("Invalid template at line {}: {}", line, message))
+}
+
+enum Token {
+    Text(String),
+    /// Content of `{{ }}`
+    Output(String),
+    /// Content of `{% %}`
+    Tag(String),
+}
+
+enum Node {
+    Text(String),
+    Output(Vec<String>),
+    For { variable: String, list: Vec<String>, body: Vec<Node> },
+    If { path: Vec<String>, test: Test, then_nodes: Vec<Node>, else_nodes: Vec<Node> },
+}
+
+enum Test {
+    IsTrue,
+    IsFalse,
+    Equals(String),
+    NotEquals(String),
+}
+
+/// Type of a variable, used to check the template before it is rendered
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+enum Type {
+    Text,
+    Bool,
+    Step,
+    Section,
+    Loop,
+    List(Box<Type>),
+}
+
+#[derive(Clone)]
+enum Value<'p> {
+    Text(String),
+    Bool(bool),
+    Step(&'p Step),
+    Section(&'p Section),
+    /// Index of the current item starting at 0 and the length of the list
+    Loop(usize, usize),
+    List(Vec<Value<'p>>),
+}
+
+/// Splits the source into text, output and tags, each with the number of the line it starts
+/// at. Lines which hold a single tag only are removed with their line break.
+fn tokenize(source: &str) -> ParseResult<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+    while !rest.is_empty() {
+        let start = match (rest.find("{{"), rest.find("{%")) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(a), None) | (None, Some(a)) => a,
+            (None, None) => rest.len(),
+        };
+        if start > 0 {
+            tokens.push((Token::Text(rest[..start].to_string()), line));
+            line += rest[..start].matches('\n').count();
+            rest = &rest[start..];
+            continue;
+        }
+        let is_tag = rest.starts_with("{%");
+        let end_marker = if is_tag { "%}" } else { "}}" };
+        let end = try!(rest[2..].find(end_marker).map(|end| end + 2).ok_or_else(|| {
+            (line, format!("{} is not closed by {}", &rest[..2], end_marker))
+        }));
+        let content = rest[2..end].trim().to_string();
+        if content.contains('\n') {
+            return Err((line, format!("{} spans several lines", &rest[..2])));
+        }
+        tokens.push((if is_tag { Token::Tag(content) } else { Token::Output(content) }, line));
+        rest = &rest[end + 2..];
+    }
+    remove_tag_lines(&mut tokens);
+    Ok(tokens)
+}
+
+fn remove_tag_lines(tokens: &mut Vec<(Token, usize)>) {
+    let tag_lines = (0..tokens.len()).filter(|&index| is_tag_line(tokens, index))
+                                     .collect::<Vec<_>>();
+    for index in tag_lines {
+        if index > 0 {
+            if let Token::Text(ref mut text) = tokens[index - 1].0 {
+                let length = text.rfind('\n').map(|i| i + 1).unwrap_or(0);
+                text.truncate(length);
+            }
+        }
+        if let Some(&mut (Token::Text(ref mut text), _)) = tokens.get_mut(index + 1) {
+            let length = text.find('\n').map(|i| i + 1).unwrap_or(text.len());
+            *text = text[length..].to_string();
+        }
+    }
+}
+
+/// Whether the token at `index` is a tag with nothing but whitespace around it on its line
+fn is_tag_line(tokens: &[(Token, usize)], index: usize) -> bool {
+    if let Token::Tag(_) = tokens[index].0 {} else {
+        return false;
+    }
+    let starts_line = match index.checked_sub(1).map(|i| &tokens[i].0) {
+        None => true,
+        Some(&Token::Text(ref text)) => {
+            let last_line = text.rsplit('\n').next().unwrap();
+            (text.contains('\n') || index == 1) && last_line.trim().is_empty()
+        }
+        Some(_) => false,
+    };
+    let ends_line = match tokens.get(index + 1).map(|t| &t.0) {
+        None => true,
+        Some(&Token::Text(ref text)) => {
+            text.split('\n').next().unwrap().trim().is_empty() &&
+            (text.contains('\n') || index + 2 == tokens.len())
+        }
+        Some(_) => false,
+    };
+    starts_line && ends_line
+}
+
+struct Parser<'t> {
+    tokens: &'t [(Token, usize)],
+    position: usize,
+    /// Variables in scope with their types, inner ones last
+    scope: Vec<(String, Type)>,
+}
+
+impl<'t> Parser<'t> {
+    /// Parses nodes until one of `terminators` or the end of the template
+    /// # Returns
+    /// The nodes and the terminator found, `None` at the end of the template
+    /// # Arguments
+    /// * `closing_tag` tag which must be found before the end of the template, e.g. `endif`
+    /// * `opened_at` line of the tag which expects a terminator
+    fn parse_nodes(&mut self, terminators: &[&str], closing_tag: Option<&str>, opened_at: usize)
+                   -> ParseResult<(Vec<Node>, Option<String>)> {
+        let mut nodes = Vec::new();
+        while self.position < self.tokens.len() {
+            let (ref token, line) = self.tokens[self.position];
+            self.position += 1;
+            match *token {
+                Token::Text(ref text) => nodes.push(Node::Text(text.clone())),
+                Token::Output(ref expression) => {
+                    let (path, value_type) = try!(self.check_path(expression, line));
+                    if value_type != Type::Text && value_type != Type::Bool {
+                        return Err((line, format!("{} cannot be printed", expression)));
+                    }
+                    nodes.push(Node::Output(path));
+                }
+                Token::Tag(ref tag) => {
+                    let keyword = tag.split_whitespace().next().unwrap_or("");
+                    if terminators.contains(&keyword) {
+                        if tag != keyword {
+                            return Err((line, format!("Unexpected text after {}", keyword)));
+                        }
+                        return Ok((nodes, Some(keyword.to_string())));
+                    }
+                    match keyword {
+                        "for" => nodes.push(try!(self.parse_for(tag, line))),
+                        "if" => nodes.push(try!(self.parse_if(tag, line))),
+                        _ => return Err((line, format!("Unexpected tag {{% {} %}}", tag))),
+                    }
+                }
+            }
+        }
+        match closing_tag {
+            Some(closing_tag) => {
+                Err((opened_at, format!("Tag is not closed by {{% {} %}}", closing_tag)))
+            }
+            None => Ok((nodes, None)),
+        }
+    }
+
+    /// Parses `for <variable> in <list>` and its body
+    fn parse_for(&mut self, tag: &str, line: usize) -> ParseResult<Node> {
+        let words = tag.split_whitespace().collect::<Vec<_>>();
+        if words.len() != 4 || words[2] != "in" {
+            return Err((line, String::from("Expected {% for <variable> in <list> %}")));
+        }
+        let (list, list_type) = try!(self.check_path(words[3], line));
+        let item_type = match list_type {
+            Type::List(item_type) => *item_type,
+            _ => return Err((line, format!("{} is not a list", words[3]))),
+        };
+        self.scope.push((words[1].to_string(), item_type));
+        self.scope.push((String::from("loop"), Type::Loop));
+        let body = try!(self.parse_nodes(&["endfor"], Some("endfor"), line)).0;
+        self.scope.truncate(self.scope.len() - 2);
+        Ok(Node::For { variable: words[1].to_string(), list: list, body: body })
+    }
+
+    /// Parses `if <condition>` and its branches
+    fn parse_if(&mut self, tag: &str, line: usize) -> ParseResult<Node> {
+        let condition = tag["if".len()..].trim();
+        let (expression, test) = if condition.starts_with("not ") {
+            (condition["not ".len()..].trim(), Test::IsFalse)
+        } else if let Some(index) = find_operator(condition) {
+            let literal = condition[index + 2..].trim();
+            if literal.len() < 2 || !literal.starts_with('"') || !literal.ends_with('"') {
+                return Err((line, format!("Expected a quoted string, found {}", literal)));
+            }
+            let literal = literal[1..literal.len() - 1].to_string();
+            let test = if &condition[index..index + 2] == "==" {
+                Test::Equals(literal)
+            } else {
+                Test::NotEquals(literal)
+            };
+            (condition[..index].trim(), test)
+        } else {
+            (condition, Test::IsTrue)
+        };
+        let (path, value_type) = try!(self.check_path(expression, line));
+        let is_comparison = match test {
+            Test::Equals(_) | Test::NotEquals(_) => true,
+            Test::IsTrue | Test::IsFalse => false,
+        };
+        if is_comparison && value_type != Type::Text && value_type != Type::Bool {
+            return Err((line, format!("{} cannot be compared with a string", expression)));
+        }
+        let (then_nodes, terminator) = try!(self.parse_nodes(&["else", "endif"], Some("endif"),
+                                                             line));
+        let else_nodes = if terminator.as_ref().map(String::as_str) == Some("else") {
+            try!(self.parse_nodes(&["endif"], Some("endif"), line)).0
+        } else {
+            Vec::new()
+        };
+        Ok(Node::If { path: path, test: test, then_nodes: then_nodes, else_nodes: else_nodes })
+    }
+
+    /// Checks that `expression`, e.g. `step.name`, refers to a variable in scope
+    /// # Returns
+    /// Parts of the path and the type of the variable
+    fn check_path(&self, expression: &str, line: usize)
+                  -> ParseResult<(Vec<String>, Type)> {
+        let path = expression.split('.').map(str::to_string).collect::<Vec<_>>();
+        let mut value_type = try!(self.scope
+                                      .iter()
+                                      .rev()
+                                      .find(|&&(ref name, _)| *name == path[0])
+                                      .map(|&(_, ref value_type)| value_type.clone())
+                                      .ok_or_else(|| {
+                                          (line, format!("Unknown variable {}", path[0]))
+                                      }));
+        for (index, field) in path.iter().enumerate().skip(1) {
+            value_type = try!(field_type(&value_type, field).ok_or_else(|| {
+                (line, format!("{} has no field {}", path[..index].join("."), field))
+            }));
+        }
+        Ok((path, value_type))
+    }
+}
+
+/// Returns the index of the first `==` or `!=` outside a quoted string
+fn find_operator(condition: &str) -> Option<usize> {
+    let mut is_quoted = false;
+    for (index, character) in condition.char_indices() {
+        if character == '"' {
+            is_quoted = !is_quoted;
+        } else if !is_quoted &&
+                  (condition[index..].starts_with("==") || condition[index..].starts_with("!=")) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+fn initial_types() -> Vec<(String, Type)> {
+    vec![(String::from("os"), Type::Step),
+         (String::from("steps"), Type::List(Box::new(Type::Step))),
+         (String::from("requested"), Type::List(Box::new(Type::Step)))]
+}
+
+fn initial_values<'p>(plan: &'p Plan) -> Vec<(String, Value<'p>)> {
+    let steps = plan.steps().iter().map(Value::Step).collect();
+    let requested = plan.requested_steps().into_iter().map(Value::Step).collect();
+    let mut values = vec![(String::from("steps"), Value::List(steps)),
+                          (String::from("requested"), Value::List(requested))];
+    if let Some(os) = plan.steps().iter().find(|step| step.reason == Reason::Os) {
+        values.push((String::from("os"), Value::Step(os)));
+    }
+    values
+}
+
+fn field_type(value_type: &Type, field: &str) -> Option<Type> {
+    match (value_type, field) {
+        (&Type::Step, "name") | (&Type::Step, "version") | (&Type::Step, "full_name") |
+        (&Type::Step, "bunch") | (&Type::Step, "path") | (&Type::Step, "reason") |
+        (&Type::Section, "kind") | (&Type::Loop, "index") => Some(Type::Text),
+        (&Type::Loop, "first") | (&Type::Loop, "last") => Some(Type::Bool),
+        (&Type::Step, "sections") => Some(Type::List(Box::new(Type::Section))),
+        (&Type::Step, "dependencies") | (&Type::Section, "items") => {
+            Some(Type::List(Box::new(Type::Text)))
+        }
+        _ => None,
+    }
+}
+
+/// # Panics
+/// If the field is not checked by `field_type`
+fn field<'p>(value: &Value<'p>, field: &str) -> Value<'p> {
+    match (value, field) {
+        (&Value::Step(step), "name") => Value::Text(step.name.clone()),
+        (&Value::Step(step), "version") => Value::Text(step.version.to_string()),
+        (&Value::Step(step), "full_name") => Value::Text(step.full_name()),
+        (&Value::Step(step), "bunch") => Value::Text(step.bunch.clone()),
+        (&Value::Step(step), "path") => Value::Text(step.path.display().to_string()),
+        (&Value::Step(step), "reason") => {
+            let reason = match step.reason {
+                Reason::Os => "os",
+                Reason::Requested => "requested",
+                Reason::Dependency => "dependency",
+            };
+            Value::Text(reason.to_string())
+        }
+        (&Value::Step(step), "sections") => {
+            Value::List(step.sections.iter().map(Value::Section).collect())
+        }
+        (&Value::Step(step), "dependencies") => {
+            Value::List(step.dependencies.iter().map(|name| Value::Text(name.clone())).collect())
+        }
+        (&Value::Section(section), "kind") => Value::Text(section.kind.keyword().to_string()),
+        (&Value::Section(section), "items") => {
+            Value::List(section.items.iter().map(|item| Value::Text(item.clone())).collect())
+        }
+        (&Value::Loop(index, _), "index") => Value::Text((index + 1).to_string()),
+        (&Value::Loop(index, _), "first") => Value::Bool(index == 0),
+        (&Value::Loop(index, length), "last") => Value::Bool(index + 1 == length),
+        _ => unreachable!("Field {} is not checked", field),
+    }
+}
+
+/// Returns the value of a checked path. `os` and its fields are an empty list if the plan has
+/// no OS step, which is printed as an empty text.
+fn lookup<'p>(scope: &[(String, Value<'p>)], path: &[String]) -> Value<'p> {
+    let value = scope.iter().rev().find(|&&(ref name, _)| *name == path[0]).map(|v| v.1.clone());
+    match value {
+        Some(value) => path[1..].iter().fold(value, |value, name| field(&value, name)),
+        None => Value::List(Vec::new()),
+    }
+}
+
+fn to_text(value: &Value) -> String {
+    match *value {
+        Value::Text(ref text) => text.clone(),
+        Value::Bool(value) => value.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn is_true(value: &Value) -> bool {
+    match *value {
+        Value::Text(ref text) => !text.is_empty(),
+        Value::Bool(value) => value,
+        Value::List(ref items) => !items.is_empty(),
+        Value::Step(_) | Value::Section(_) | Value::Loop(..) => true,
+    }
+}
+
+fn render<'p>(nodes: &[Node], scope: &mut Vec<(String, Value<'p>)>, code: &mut String) {
+    for node in nodes {
+        match *node {
+            Node::Text(ref text) => code.push_str(text),
+            Node::Output(ref path) => code.push_str(&to_text(&lookup(scope, path))),
+            Node::For { ref variable, ref list, ref body } => {
+                let items = match lookup(scope, list) {
+                    Value::List(items) => items,
+                    _ => unreachable!("For loops over checked lists only"),
+                };
+                let length = items.len();
+                for (index, item) in items.into_iter().enumerate() {
+                    scope.push((variable.clone(), item));
+                    scope.push((String::from("loop"), Value::Loop(index, length)));
+                    render(body, scope, code);
+                    scope.truncate(scope.len() - 2);
+                }
+            }
+            Node::If { ref path, ref test, ref then_nodes, ref else_nodes } => {
+                let value = lookup(scope, path);
+                let is_met = match *test {
+                    Test::IsTrue => is_true(&value),
+                    Test::IsFalse => !is_true(&value),
+                    Test::Equals(ref literal) => to_text(&value) == *literal,
+                    Test::NotEquals(ref literal) => to_text(&value) != *literal,
+                };
+                render(if is_met { then_nodes } else { else_nodes }, scope, code);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use error::Error;
+    use formatters::Formatter;
+    use plan::{Plan, Reason, Step};
+    use slice::{Kind, Section};
+    use version;
+    use super::TemplateFormatter;
+
+    fn create_plan() -> Plan {
+        let create_step = |name: &str, reason: Reason, sections: Vec<Section>| {
+            Step { name: String::from(name), version: version::parse("1.0.0").unwrap(),
+                   bunch: String::from("slices-0.0.0"), path: PathBuf::from(name),
+                   reason: reason, dependencies: Vec::new(), sections: sections }
+        };
+        let run = |items: &[&str]| {
+            Section::new(Kind::Run, items.iter().map(|item| item.to_string()).collect())
+        };
+        Plan::from_steps(vec![create_step("debian", Reason::Os, vec![run(&["apt-get update"])]),
+                              create_step("wget", Reason::Dependency,
+                                          vec![Section::new(Kind::Env,
+                                                            vec![String::from("A=1")]),
+                                               run(&["apt-get install wget"])]),
+                              create_step("ruby", Reason::Requested,
+                                          vec![run(&["make", "make install"])])])
+    }
+
+    fn render(template: &str) -> String {
+        TemplateFormatter::parse(template).unwrap().generate_code(&create_plan())
+    }
+
+    fn parse_error(template: &str) -> String {
+        match TemplateFormatter::parse(template) {
+            Err(Error::Parse(message)) => message,
+            _ => panic!("Parse error expected for {}", template),
+        }
+    }
+
+    #[test]
+    fn render_loops_and_conditionals() {
+        let template = "# {{ os.full_name }}
+{% for step in steps %}
+  {% if step.reason != \"os\" %}
+echo '{{ loop.index }}. {{ step.name }} from {{ step.bunch }} ({{ step.reason }})'
+  {% endif %}
+  {% for section in step.sections %}
+    {% if section.kind == \"RUN\" %}
+      {% for item in section.items %}
+{{ item }}{% if not loop.last %} &&{% endif %}
+      {% endfor %}
+    {% else %}
+# {{ section.kind }} skipped
+    {% endif %}
+  {% endfor %}
+{% endfor %}
+";
+        assert_eq!(render(template), "# debian-1.0.0
+apt-get update
+echo '2. wget from slices-0.0.0 (dependency)'
+# ENV skipped
+apt-get install wget
+echo '3. ruby from slices-0.0.0 (requested)'
+make &&
+make install
+");
+    }
+
+    #[test]
+    fn comparison_literal_may_contain_operator() {
+        let template = "{% if os.name != \"a==b\" %}x{% endif %}\
+                        {% if os.name == \"a!=b\" %}y{% else %}z{% endif %}";
+        assert_eq!(render(template), "xz");
+    }
+
+    #[test]
+    fn step_renders_template_for_single_step() {
+        let formatter = TemplateFormatter::parse("{% for s in requested %}{{ s.name }}{% endfor %}")
+                            .unwrap();
+        let plan = create_plan();
+        assert_eq!(formatter.step(&plan.steps()[2]), "ruby");
+        assert_eq!(formatter.step(&plan.steps()[1]), "");
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        assert_eq!(parse_error("a\n{% for step in steps %}\n{{ step.name }}\n"),
+                   "Invalid template at line 2: Tag is not closed by {% endfor %}");
+        assert_eq!(parse_error("{% for step in steps %}\n{{ step.size }}\n{% endfor %}"),
+                   "Invalid template at line 2: step has no field size");
+        assert_eq!(parse_error("\n\n{{ slice.name }}"),
+                   "Invalid template at line 3: Unknown variable slice");
+        assert_eq!(parse_error("a\n{% endif %}"),
+                   "Invalid template at line 2: Unexpected tag {% endif %}");
+        assert_eq!(parse_error("{{ os.name }}\n{{ steps }}"),
+                   "Invalid template at line 2: steps cannot be printed");
+        assert_eq!(parse_error("{% if os.name == debian %}{% endif %}"),
+                   "Invalid template at line 1: Expected a quoted string, found debian");
+        assert_eq!(parse_error("{{ os.name }}\n\n{{ os.name"),
+                   "Invalid template at line 3: {{ is not closed by }}");
+        assert_eq!(parse_error("a\n{%}"), "Invalid template at line 2: {% is not closed by %}");
+        assert_eq!(parse_error("a\n{% if os.name %}\nb\n"),
+                   "Invalid template at line 2: Tag is not closed by {% endif %}");
+        assert_eq!(parse_error("{% if os.name %}\n{% else %}\nb\n"),
+                   "Invalid template at line 1: Tag is not closed by {% endif %}");
+    }
+}
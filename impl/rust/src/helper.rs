@@ -1,6 +1,14 @@
-use std::io::ErrorKind;
-use std::fs::metadata;
-use std::path::Path;
+use std::env;
+use std::io::{ErrorKind, Read, Write, stderr};
+use std::fs::{create_dir_all, metadata, File};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use sha2::{Digest, Sha256};
+use error::{Error, Result};
+use slice::List;
+
+static TEMPORARY_DIRECTORY_COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
 
 pub fn assert_slice_root_exists(slice_root_directory: &Path) {
     if let Err(error) = metadata(slice_root_directory) {
@@ -14,6 +22,73 @@ pub fn assert_slice_root_exists(slice_root_directory: &Path) {
     }
 }
 
+/// Prints warnings collected while the list was loaded
+pub fn print_diagnostics(list: &List) {
+    for diagnostic in list.diagnostics() {
+        let _ = writeln!(stderr(), "Warning: {}", diagnostic);
+    }
+}
+
+/// Prints the warnings of several lists, each distinct warning once
+pub fn print_unique_diagnostics(lists: &[List]) {
+    let mut diagnostics = lists.iter().flat_map(|list| list.diagnostics()).collect::<Vec<_>>();
+    diagnostics.sort();
+    diagnostics.dedup();
+    for diagnostic in diagnostics {
+        let _ = writeln!(stderr(), "Warning: {}", diagnostic);
+    }
+}
+
+/// Creates a new empty directory in the system temporary directory
+pub fn create_temporary_directory(prefix: &str) -> Result<PathBuf> {
+    let number = TEMPORARY_DIRECTORY_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let mut path = env::temp_dir();
+    path.push(format!("{}-{}-{}", prefix, process::id(), number));
+    try!(create_dir_all(&path).map_err(|e| Error::io(&path, e)));
+    Ok(path)
+}
+
+/// Returns SHA-256 of `bytes` as lowercase hex
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::default();
+    hasher.input(bytes);
+    hasher.result().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Returns SHA-256 of the content of the file at `path` as lowercase hex
+pub fn file_sha256_hex(path: &Path) -> Result<String> {
+    let mut file = try!(File::open(path).map_err(|e| Error::io(path, e)));
+    let mut bytes = Vec::new();
+    try!(file.read_to_end(&mut bytes).map_err(|e| Error::io(path, e)));
+    Ok(sha256_hex(&bytes))
+}
+
+/// Checks `text` against a glob `pattern`, where `*` matches any characters and `?` matches
+/// a single character
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and of the text it is matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_position, matched)) = star {
+            star = Some((star_position, matched + 1));
+            p = star_position + 1;
+            t = matched + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 #[cfg(test)]
 mod tests {
     use std::env::current_dir;
@@ -31,4 +106,14 @@ mod tests {
         let path = Path::new("/path/that/does/not/exist");
         super::assert_slice_root_exists(path);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn glob_matches_stars_and_question_marks() {
+        assert!(super::glob_matches("ruby*", "ruby-dev"));
+        assert!(super::glob_matches("*ruby*", "ruby"));
+        assert!(super::glob_matches("lib?2*", "libc2-dev"));
+        assert!(super::glob_matches("g++", "g++"));
+        assert!(!super::glob_matches("ruby", "ruby-dev"));
+        assert!(!super::glob_matches("*dev", "ruby-devel"));
+    }
+}
@@ -1,35 +1,153 @@
 extern crate curl;
+extern crate flate2;
 extern crate rustc_serialize;
 extern crate semver;
+extern crate sha2;
+extern crate tar;
+extern crate toml;
 extern crate zip;
 
-pub use commands::{Command, FetchCommand, FindCommand, MakeCommand};
+pub use commands::{BuildResult, Command, EnvCommand, FetchCommand, FindCommand, ListCommand,
+                   MakeCommand, TestCommand};
+pub use error::{Error, Result};
+pub use formatters::Formatter;
+pub use plan::Plan;
+pub use report::FindReport;
+pub use slice::{Kind, Section};
 
 macro_rules! assert_not_empty {
     ($e:expr) => (assert!(!$e.is_empty(), "{} is empty", stringify!($e)));
 }
 
-use semver::Version;
+use std::str::FromStr;
+use version::SliceVersion;
 
 pub mod commands;
+pub mod error;
+pub mod formatters;
+pub mod lock;
+pub mod manifest;
 pub mod options_parse;
 pub mod os;
+pub mod plan;
+pub mod report;
+pub mod root;
 pub mod version;
 
 mod helper;
 mod slice;
-mod formatters;
+mod source;
 
+/// How the version of a slice is compared with the requested version
+///
+/// Requests are written as `name=1.2.3`, `name<=1.2`, `name>=1.2`, `name^1`, `name@latest` or,
+/// like `name>=1.2`, as `name-1.2`.
 #[derive(Clone)]
 #[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(PartialOrd)]
+#[derive(Eq)]
 pub enum VersionMatchStrategy {
     Exact,
 	ExactOrLesser,
 	ExactOrGreater,
+	/// At least the requested version without a change of the first non-zero component,
+	/// e.g. `^1.2` matches `1.9.0`, but not `2.0.0`
+	Compatible,
+	/// Any version, the latest one is chosen
+	Latest,
 }
 
+/// What to do with two slices having the same name, version and bunch version
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum DuplicateSlicePolicy {
+    /// Refuse to build the list of slices
+    Deny,
+    /// Keep the first slice, ignore the other and report a warning
+    Warn,
+}
+
+impl FromStr for DuplicateSlicePolicy {
+    type Err = &'static str;
+    fn from_str(str: &str) -> std::result::Result<Self, Self::Err> {
+        match str {
+            "deny" => Ok(DuplicateSlicePolicy::Deny),
+            "warn" => Ok(DuplicateSlicePolicy::Warn),
+            _ => Err("Unknown duplicate slice policy. Available policies = [deny, warn]")
+        }
+    }
+}
+
+/// Whether prerelease slices and slices of prerelease bunches can be selected
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum PrereleasePolicy {
+    /// Select prereleases only for requests which name a prerelease version, e.g. `jekyll-3-beta`
+    Exclude,
+    /// Select prereleases like releases, set by the `--pre` flag
+    Include,
+}
+
+/// When fetched archives are checked against a published SHA-256 checksum manifest
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum ChecksumPolicy {
+    /// Check the checksum if the source publishes it
+    Auto,
+    /// Refuse sources which publish no checksum
+    Require,
+    /// Do not check checksums
+    Skip,
+}
+
+impl FromStr for ChecksumPolicy {
+    type Err = &'static str;
+    fn from_str(str: &str) -> std::result::Result<Self, Self::Err> {
+        match str {
+            "auto" => Ok(ChecksumPolicy::Auto),
+            "require" => Ok(ChecksumPolicy::Require),
+            "skip" => Ok(ChecksumPolicy::Skip),
+            _ => Err("Unknown checksum policy. Available policies = [auto, require, skip]")
+        }
+    }
+}
+
+/// How `make` uses the lockfile
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum LockMode {
+    /// Resolve slices without reading or writing the lockfile
+    Ignore,
+    /// Resolve slices and record the resolution in the lockfile
+    Write,
+    /// Reproduce the resolution recorded in the lockfile
+    Locked,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
 pub struct RequestedSlice {
     pub name: String,
-	pub version: Version,
+	pub version: SliceVersion,
 	pub version_match_strategy: VersionMatchStrategy,
+}
+
+impl RequestedSlice {
+    /// Parses a request like `ruby<=2.2`, see `VersionMatchStrategy`
+    /// # Errors
+    /// `Error::Parse` if the name is empty, the operator is unknown or the version is invalid
+    pub fn parse(request: &str) -> Result<RequestedSlice> {
+        let (name, version, strategy) = try!(version::parse_request(request));
+        Ok(RequestedSlice { name: name, version: version, version_match_strategy: strategy })
+    }
 }
\ No newline at end of file
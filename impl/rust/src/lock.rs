@@ -0,0 +1,260 @@
+//! Lockfile which records the slices a `make` resolved to
+//!
+//! Every line after the comments describes one slice in dependency order, the OS slice first:
+//!
+//! ```text
+//! os debian 8.2.0 slices-du-0.0.2 <SHA-256 of the slice file>
+//! slice jekyll 3.0.0-beta.9 slices-du-0.0.2 <SHA-256 of the slice file>
+//! ```
+
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use error::{Error, Result};
+use helper;
+use slice::{List, Slice};
+use version::{self, SliceVersion};
+
+/// Name of the lockfile `make` writes and reads by default
+pub const DEFAULT_FILE_NAME: &'static str = "sb.lock";
+
+const OS_KIND: &'static str = "os";
+const SLICE_KIND: &'static str = "slice";
+
+/// Slice pinned by name, version, bunch and content
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct LockedSlice {
+    pub name: String,
+    pub version: SliceVersion,
+    /// Directory name of the bunch, e.g. `slices-1.0.0`
+    pub bunch: String,
+    /// SHA-256 of the slice file as lowercase hex
+    pub sha256: String,
+}
+
+impl LockedSlice {
+    fn from_slice(slice: &Slice) -> Result<LockedSlice> {
+        Ok(LockedSlice {
+            name: slice.name().clone(),
+            version: slice.version().clone(),
+            bunch: slice.bunch().to_string(),
+            sha256: try!(helper::file_sha256_hex(slice.path())),
+        })
+    }
+
+    /// Finds the locked slice in `list` and checks that its file is not changed
+    /// # Errors
+    /// `Error::Resolution` if the slice is missing or its file has another checksum
+    fn find_in<'a>(&self, list: &'a List) -> Result<&'a Slice> {
+        let slice = try!(list.find_slice_in_bunch(&self.name, &self.version, &self.bunch)
+                             .ok_or_else(|| {
+                                 Error::Resolution(format!("Locked slice {} is missing", self))
+                             }));
+        let sha256 = try!(helper::file_sha256_hex(slice.path()));
+        if sha256 != self.sha256 {
+            return Err(Error::Resolution(format!("Locked slice {} is changed. Path = {}, \
+                                                  expected SHA-256 = {}, actual = {}",
+                                                 self, slice.path().display(), self.sha256,
+                                                 sha256)));
+        }
+        Ok(slice)
+    }
+}
+
+impl Display for LockedSlice {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        write!(formatter, "{}-{} from bunch {}", self.name, self.version, self.bunch)
+    }
+}
+
+/// Resolution of a `make`: the OS slice and the other slices in dependency order
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Lockfile {
+    pub os: LockedSlice,
+    pub slices: Vec<LockedSlice>,
+}
+
+impl Lockfile {
+    /// Locks `os_slice` and `slices` which are expected to be in dependency order
+    /// # Errors
+    /// `Error::Io` if a slice file cannot be read
+    pub fn from_slices(os_slice: &Slice, slices: &[&Slice]) -> Result<Lockfile> {
+        let mut locked_slices = Vec::with_capacity(slices.len());
+        for slice in slices {
+            locked_slices.push(try!(LockedSlice::from_slice(slice)));
+        }
+        Ok(Lockfile { os: try!(LockedSlice::from_slice(os_slice)), slices: locked_slices })
+    }
+
+    /// # Errors
+    /// `Error::Parse` if a line is invalid, e.g. its SHA-256 is not 64 lowercase hex digits,
+    /// or there is not exactly one OS slice
+    pub fn parse(text: &str) -> Result<Lockfile> {
+        let mut os = None;
+        let mut slices = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid_line = || {
+                Error::Parse(format!("Invalid line {} of the lockfile: {}", index + 1, line))
+            };
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 5 {
+                return Err(invalid_line());
+            }
+            let version = try!(version::parse(parts[2]).map_err(|_| invalid_line()));
+            if !is_sha256_hex(parts[4]) {
+                return Err(invalid_line());
+            }
+            let slice = LockedSlice { name: parts[1].to_string(), version: version,
+                                      bunch: parts[3].to_string(), sha256: parts[4].to_string() };
+            match parts[0] {
+                OS_KIND if os.is_none() => os = Some(slice),
+                SLICE_KIND => slices.push(slice),
+                _ => return Err(invalid_line()),
+            }
+        }
+        match os {
+            Some(os) => Ok(Lockfile { os: os, slices: slices }),
+            None => Err(Error::Parse(String::from("The lockfile has no OS slice"))),
+        }
+    }
+
+    /// # Errors
+    /// * `Error::Io` if the file cannot be read
+    /// * `Error::Parse` if the file is not a valid lockfile
+    pub fn read(path: &Path) -> Result<Lockfile> {
+        let mut file = try!(File::open(path).map_err(|e| Error::io(path, e)));
+        let mut text = String::new();
+        try!(file.read_to_string(&mut text).map_err(|e| Error::io(path, e)));
+        Lockfile::parse(&text)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut file = try!(File::create(path).map_err(|e| Error::io(path, e)));
+        file.write_all(self.to_string().as_bytes()).map_err(|e| Error::io(path, e))
+    }
+
+    /// Returns the OS slice and the other locked slices from `list` in the locked order
+    /// # Errors
+    /// `Error::Resolution` if a locked slice is missing in `list` or its file is changed
+    pub fn resolve<'a>(&self, list: &'a List) -> Result<Vec<&'a Slice>> {
+        let mut slices = vec![try!(self.os.find_in(list))];
+        for locked_slice in &self.slices {
+            slices.push(try!(locked_slice.find_in(list)));
+        }
+        Ok(slices)
+    }
+}
+
+fn is_sha256_hex(text: &str) -> bool {
+    text.len() == 64 && text.chars().all(|c| c.is_digit(16) && !c.is_uppercase())
+}
+
+impl Display for Lockfile {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        try!(writeln!(formatter, "# Generated by `sb make --lock`, do not edit"));
+        try!(writeln!(formatter, "# <kind> <name> <version> <bunch> <sha256>"));
+        let os = Some((OS_KIND, &self.os)).into_iter();
+        for (kind, slice) in os.chain(self.slices.iter().map(|s| (SLICE_KIND, s))) {
+            try!(writeln!(formatter, "{} {} {} {} {}", kind, slice.name, slice.version,
+                          slice.bunch, slice.sha256));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use {DuplicateSlicePolicy, PrereleasePolicy, VersionMatchStrategy};
+    use error::Error;
+    use os::Os;
+    use slice::List;
+    use version;
+    use super::{Lockfile, LockedSlice};
+
+    fn load_list() -> List {
+        let mut path = env::current_dir().expect("Current dir is not set").to_path_buf();
+        path.push("test_slices");
+        let os = Os::parse("debian").unwrap();
+        List::new(&path, &os, DuplicateSlicePolicy::Deny, PrereleasePolicy::Include).unwrap()
+    }
+
+    fn lock_jekyll(list: &List) -> Lockfile {
+        let jekyll = list.find_slice("jekyll", &version::zero(),
+                                     VersionMatchStrategy::ExactOrGreater).unwrap();
+        let slices = list.dependency_order(&[jekyll]).unwrap();
+        Lockfile::from_slices(list.os_slice().unwrap(), &slices).unwrap()
+    }
+
+    #[test]
+    fn lockfile_is_parsed_back() {
+        let list = load_list();
+        let lockfile = lock_jekyll(&list);
+        assert_eq!(lockfile.os.name, "debian");
+        assert_eq!(lockfile.slices.last().unwrap().name, "jekyll");
+        assert_eq!(Lockfile::parse(&lockfile.to_string()).unwrap(), lockfile);
+    }
+
+    #[test]
+    fn resolve_returns_locked_slices_in_order() {
+        let list = load_list();
+        let lockfile = lock_jekyll(&list);
+        let names = lockfile.resolve(&list).unwrap().iter().map(|s| s.name().clone())
+                            .collect::<Vec<_>>();
+        let mut expected = vec![lockfile.os.name.clone()];
+        expected.extend(lockfile.slices.iter().map(|s| s.name.clone()));
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn resolve_fails_if_slice_is_changed() {
+        let list = load_list();
+        let mut lockfile = lock_jekyll(&list);
+        lockfile.slices[0].sha256 = String::from("0");
+        match lockfile.resolve(&list) {
+            Err(Error::Resolution(ref message)) => assert!(message.contains("is changed")),
+            result => panic!("Unexpected result {:?}", result.map(|s| s.len())),
+        }
+    }
+
+    #[test]
+    fn resolve_fails_if_slice_is_missing() {
+        let list = load_list();
+        let mut lockfile = lock_jekyll(&list);
+        lockfile.slices.push(LockedSlice { name: String::from("nginx"),
+                                           version: version::parse("99.0.0").unwrap(),
+                                           bunch: String::from("slices-du-0.0.2"),
+                                           sha256: String::from("0") });
+        match lockfile.resolve(&list) {
+            Err(Error::Resolution(ref message)) => assert!(message.contains("is missing")),
+            result => panic!("Unexpected result {:?}", result.map(|s| s.len())),
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid_lines() {
+        let sha256 = "0123456789abcdef".repeat(4);
+        let os = format!("os debian 8.2.0 slices-1.0.0 {}\n", sha256);
+        let slice = format!("slice jekyll 1.0.0 slices-1.0.0 {}\n", sha256);
+        assert!(Lockfile::parse(&format!("{}{}", os, slice)).is_ok());
+        let invalid_lockfiles = [format!("{}slice jekyll 1.0.0 slices-1.0.0 abc\n", os),
+                                 format!("{}slice jekyll 1.0.0 slices-1.0.0 {}\n", os,
+                                         sha256.to_uppercase()),
+                                 format!("{}slice jekyll 1.0.0 slices-1.0.0\n", os),
+                                 format!("{}slice jekyll x slices-1.0.0 {}\n", os, sha256),
+                                 format!("{}layer jekyll 1.0.0 slices-1.0.0 {}\n", os, sha256),
+                                 format!("{}{}", os, os),
+                                 slice];
+        for lockfile in &invalid_lockfiles {
+            assert!(Lockfile::parse(lockfile).is_err(), "{}", lockfile);
+        }
+    }
+}
@@ -1,95 +1,157 @@
 extern crate sb;
-use std::env::current_dir;
+use std::env::{args, current_dir};
+use std::io::{stderr, Write};
 use std::path::PathBuf;
-use sb::{RequestedSlice, VersionMatchStrategy};
-use sb::options_parse::{Options, parse_options};
-use sb::commands::{Command, FetchCommand, FindCommand, MakeCommand};
+use std::process::exit;
+use sb::{Error, RequestedSlice, Result};
+use sb::manifest::{self, Manifest};
+use sb::options_parse::{Invocation, Options, parse_arguments};
+use sb::commands::{Command, EnvCommand, FetchCommand, FindCommand, ListCommand, MakeCommand,
+                   TestCommand};
 use sb::os::Os;
+use sb::root::{self, Environment, Root};
 use sb::version;
 
 const DEFAULT_OS: &'static str = "debian";
-const DEFAULT_LAYER: &'static str = "jekyll";
 
 fn main() {
-    let (app_path, options, mut arguments) = parse_options();
-    if arguments.is_empty() {
-        panic!("Command expected")
+    if let Err(error) = run() {
+        let _ = writeln!(stderr(), "{}", error);
+        exit(error.exit_code());
+    }
+}
+
+fn run() -> Result<()> {
+    let arguments = args().skip(1).collect::<Vec<_>>();
+    // Help and usage errors do not depend on the manifest, which may be broken
+    match try!(parse_arguments(arguments.clone(), Options::new())) {
+        Invocation::Help(text) => {
+            print!("{}", text);
+            return Ok(());
+        }
+        Invocation::Version => {
+            println!("sb {}", version::tool());
+            return Ok(());
+        }
+        Invocation::Command(..) => {}
     }
-    let command = arguments.remove(0);
-    let command: &str = &command;
+    let current_directory = try!(current_dir().map_err(|e| Error::io(".", e)));
+    let manifest = try!(Manifest::find(&current_directory));
+    let mut options = Options::new();
+    if let Some(ref manifest) = manifest {
+        manifest.apply_to(&mut options);
+    }
+    let (command, options, arguments) = match try!(parse_arguments(arguments, options)) {
+        Invocation::Command(command, options, arguments) => (command, options, arguments),
+        _ => unreachable!("Help and version are handled before the manifest is read"),
+    };
+    let manifest = manifest.as_ref();
+    let root_option = if options.root.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(&options.root))
+    };
+    let root = try!(root::discover(&try!(Environment::current(root_option))));
     match command {
-        "find" => run_find_command(app_path, arguments),
-        "fetch" => run_fetch_command(app_path),
-        "make" => run_make_command(app_path, arguments, options),
-        _ => panic!("Unknown command \"{}\"", command)
+        "env" => EnvCommand::new(root, current_directory, options).run(),
+        "find" => run_find_command(&root, arguments, manifest, options),
+        "fetch" => run_fetch_command(&root, manifest, options),
+        "list" => run_list_command(&root, arguments, options),
+        "make" => run_make_command(&root, arguments, manifest, options),
+        "test" => run_test_command(&root, arguments, manifest, options),
+        _ => unreachable!("Command {} is not handled", command)
     }
 }
 
-fn pop_first_argument_or_take_default(arguments: &mut Vec<String>, default_value: String) -> String {
-    if arguments.len() == 0 {
-        default_value
-    } else {
-        arguments.remove(0)
+/// The OS argument takes precedence over the manifest, `DEFAULT_OS` is used if neither
+/// declares the OS
+fn get_os_from_arguments_or_manifest(arguments: &mut Vec<String>, manifest: Option<&Manifest>)
+                                     -> Result<Os> {
+    if arguments.is_empty() {
+        if let Some(os) = manifest.and_then(|m| m.os.clone()) {
+            return Ok(os);
+        }
+        arguments.push(DEFAULT_OS.to_string());
     }
+    Os::parse(&arguments.remove(0))
 }
 
-fn get_os_from_arguments_or_default(arguments: &mut Vec<String>) -> Os {
-    let os = pop_first_argument_or_take_default(arguments, DEFAULT_OS.to_string());
-    let (name, version) = version::extract_name_and_version(&os);
-    Os { name: name, version: version }
+/// The comma separated layers argument takes precedence over the slices of the manifest
+/// # Errors
+/// `Error::Usage` if neither requests a slice
+fn get_layers_from_arguments_or_manifest(arguments: &mut Vec<String>,
+                                         manifest: Option<&Manifest>)
+                                         -> Result<Vec<RequestedSlice>> {
+    if arguments.is_empty() {
+        return match manifest {
+            Some(manifest) if !manifest.slices.is_empty() => Ok(manifest.slices.clone()),
+            _ => {
+                Err(Error::Usage(format!("Slices expected as the first argument or in {}",
+                                         manifest::FILE_NAME)))
+            }
+        };
+    }
+    arguments.remove(0).split(',').map(RequestedSlice::parse).collect()
 }
 
-fn get_layers_from_arguments_or_default(arguments: &mut Vec<String>) -> Vec<RequestedSlice> {
-    let argument = pop_first_argument_or_take_default(arguments, DEFAULT_LAYER.to_string());
-    argument.split(',')
-            .map(|slice| {
-                let (name, version) = version::extract_name_and_version(slice);
-                RequestedSlice { name: name, version: version, version_match_strategy: VersionMatchStrategy::ExactOrGreater }
-            })
-            .collect()
+/// Fetches from the `--url` option or, if it is not given, from every source of the manifest
+fn run_fetch_command(root: &Root, manifest: Option<&Manifest>, options: Options)
+                     -> Result<()> {
+    let root_directory = &root.directory;
+    let slice_root_directory = root.slice_root_directory();
+    let sources = manifest.map(|m| m.sources.clone()).unwrap_or_default();
+    if !options.url.is_empty() || sources.is_empty() {
+        let mut command = try!(FetchCommand::new(root_directory, &slice_root_directory,
+                                                 &options));
+        return command.run();
+    }
+    for source in sources {
+        let options = Options { url: source, .. options.clone() };
+        let mut command = try!(FetchCommand::new(root_directory, &slice_root_directory,
+                                                 &options));
+        try!(command.run());
+    }
+    Ok(())
 }
 
-fn run_fetch_command(app_path: String) {
-    let root_directory = get_root_directory(&app_path);
-    let slice_root_directory = get_slice_root_directory(&root_directory);
-    let mut command = FetchCommand::new(&slice_root_directory);
-    command.run();
+fn run_find_command(root: &Root, mut arguments: Vec<String>, manifest: Option<&Manifest>,
+                    options: Options) -> Result<()> {
+    let layers = try!(get_layers_from_arguments_or_manifest(&mut arguments, manifest));
+    let os = try!(get_os_from_arguments_or_manifest(&mut arguments, manifest));
+    let slice_root_directory = root.slice_root_directory();
+    let mut command = FindCommand::new(layers, os, &slice_root_directory, options);
+    command.run()
 }
 
-fn run_find_command(app_path: String, mut arguments: Vec<String>) {
-    let layers = get_layers_from_arguments_or_default(&mut arguments);
-    let os = get_os_from_arguments_or_default(&mut arguments);
-    let root_directory = get_root_directory(&app_path);
-    let slice_root_directory = get_slice_root_directory(&root_directory);
-    let mut command = FindCommand::new(layers, os, &slice_root_directory);
-    command.run();
+/// Lists slices for the OS argument or, if it is not given, for every OS
+fn run_list_command(root: &Root, arguments: Vec<String>, options: Options) -> Result<()> {
+    let os = match arguments.first() {
+        Some(os) => Some(try!(Os::parse(os))),
+        None => None,
+    };
+    let slice_root_directory = root.slice_root_directory();
+    let mut command = ListCommand::new(os, &slice_root_directory, options);
+    command.run()
 }
 
-fn run_make_command(app_path: String, mut arguments: Vec<String>, options: Options) {
-    let layers = get_layers_from_arguments_or_default(&mut arguments);
-    let os = get_os_from_arguments_or_default(&mut arguments);
-    let root_directory = get_root_directory(&app_path);
-    let slice_root_directory = get_slice_root_directory(&root_directory);
+fn run_make_command(root: &Root, mut arguments: Vec<String>, manifest: Option<&Manifest>,
+                    options: Options) -> Result<()> {
+    let layers = try!(get_layers_from_arguments_or_manifest(&mut arguments, manifest));
+    let os = try!(get_os_from_arguments_or_manifest(&mut arguments, manifest));
+    let root_directory = &root.directory;
+    let slice_root_directory = root.slice_root_directory();
     let mut command = MakeCommand::new(layers, os, &root_directory,
                                        &slice_root_directory, options);
-    command.run();
+    command.run()
 }
 
-fn get_root_directory(app_path: &str) -> PathBuf {
-    let mut root_directory = PathBuf::new();
-    root_directory.push(app_path);
-    if root_directory.is_relative() {
-        let mut new_root_directory = current_dir().unwrap();
-        new_root_directory.push(root_directory);
-        root_directory = new_root_directory;
-    }
-    assert_eq!(root_directory.pop(), true);
-    root_directory.push(".sb");
-    root_directory
-}
-
-fn get_slice_root_directory(root_directory: &PathBuf) -> PathBuf {
-    let mut slice_root_directory = root_directory.clone();
-    slice_root_directory.push("slices");
-    slice_root_directory
+fn run_test_command(root: &Root, mut arguments: Vec<String>, manifest: Option<&Manifest>,
+                    options: Options) -> Result<()> {
+    let layers = try!(get_layers_from_arguments_or_manifest(&mut arguments, manifest));
+    let os = try!(get_os_from_arguments_or_manifest(&mut arguments, manifest));
+    let root_directory = &root.directory;
+    let slice_root_directory = root.slice_root_directory();
+    let mut command = TestCommand::new(layers, os, &root_directory,
+                                       &slice_root_directory, options);
+    command.run()
 }
@@ -0,0 +1,180 @@
+//! Project manifest `sb.toml` which declares the environment of a project
+//!
+//! ```toml
+//! os = "debian-8"
+//! slices = ["jekyll-3.0.0", "nginx"]
+//! format = "d"
+//! outpath = "Dockerfile"
+//! sources = ["https://example.com/slices-1.0.0.zip"]
+//! ```
+//!
+//! Slices and the OS are requested like on the command line, e.g. `ruby<=2.2` or
+//! `ruby@latest`. Every key is optional; command line arguments and options take precedence
+//! over the manifest.
+
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use toml;
+use RequestedSlice;
+use error::{Error, Result};
+use options_parse::Options;
+use os::Os;
+
+/// Name of the manifest file in the project directory
+pub const FILE_NAME: &'static str = "sb.toml";
+
+#[derive(Clone)]
+pub struct Manifest {
+    pub os: Option<Os>,
+    /// Requested slices in the declared order
+    pub slices: Vec<RequestedSlice>,
+    /// Name of the formatter, checked when the code is generated
+    pub format: Option<String>,
+    pub outpath: Option<String>,
+    /// Urls of slice sources as accepted by `fetch --url`
+    pub sources: Vec<String>,
+}
+
+impl Manifest {
+    /// # Errors
+    /// `Error::Parse` if the text is not valid TOML, has an unknown key or a value of a
+    /// wrong type
+    pub fn parse(text: &str) -> Result<Manifest> {
+        let mut parser = toml::Parser::new(text);
+        let table = match parser.parse() {
+            Some(table) => table,
+            None => {
+                let messages = parser.errors.iter().map(|error| {
+                    let (line, column) = parser.to_linecol(error.lo);
+                    format!("{}:{}: {}", line + 1, column + 1, error.desc)
+                })
+                .collect::<Vec<_>>();
+                return Err(Error::Parse(format!("Invalid manifest. {}", messages.join("; "))));
+            }
+        };
+        let mut manifest = Manifest { os: None, slices: Vec::new(), format: None, outpath: None,
+                                      sources: Vec::new() };
+        for (key, value) in &table {
+            match key.as_str() {
+                "os" => manifest.os = Some(try!(Os::parse(try!(string_value(key, value))))),
+                "slices" => {
+                    for slice in try!(string_values(key, value)) {
+                        manifest.slices.push(try!(RequestedSlice::parse(slice)));
+                    }
+                }
+                "format" => manifest.format = Some(try!(string_value(key, value)).to_string()),
+                "outpath" => manifest.outpath = Some(try!(string_value(key, value)).to_string()),
+                "sources" => {
+                    manifest.sources = try!(string_values(key, value)).into_iter()
+                                                                      .map(str::to_string)
+                                                                      .collect();
+                }
+                _ => {
+                    return Err(Error::Parse(format!("Unknown key in manifest = {}. Available \
+                                                     keys = [os, slices, format, outpath, \
+                                                     sources]",
+                                                    key)));
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Reads `sb.toml` from `directory`
+    /// # Returns
+    /// `None` if there is no manifest in `directory`
+    pub fn find(directory: &Path) -> Result<Option<Manifest>> {
+        let path = directory.join(FILE_NAME);
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(Error::io(&path, error)),
+        };
+        let mut text = String::new();
+        try!(file.read_to_string(&mut text).map_err(|e| Error::io(&path, e)));
+        Manifest::parse(&text).map(Some).map_err(|error| {
+            match error {
+                Error::Parse(message) => {
+                    Error::Parse(format!("{}. Path = {}", message, path.display()))
+                }
+                error => error,
+            }
+        })
+    }
+
+    /// Sets the options declared in the manifest, command line options are expected to be
+    /// parsed afterwards to override them
+    pub fn apply_to(&self, options: &mut Options) {
+        if let Some(ref format) = self.format {
+            options.format = format.clone();
+        }
+        if let Some(ref outpath) = self.outpath {
+            options.outpath = outpath.clone();
+        }
+    }
+}
+
+fn string_value<'a>(key: &str, value: &'a toml::Value) -> Result<&'a str> {
+    value.as_str().ok_or_else(|| {
+        Error::Parse(format!("Manifest key {} expects a string, found {}", key,
+                             value.type_str()))
+    })
+}
+
+fn string_values<'a>(key: &str, value: &'a toml::Value) -> Result<Vec<&'a str>> {
+    let values = try!(value.as_slice().ok_or_else(|| {
+        Error::Parse(format!("Manifest key {} expects an array of strings, found {}", key,
+                             value.type_str()))
+    }));
+    values.iter().map(|value| string_value(key, value)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use error::Error;
+    use version;
+    use super::Manifest;
+
+    #[test]
+    fn parse_manifest() {
+        let manifest = Manifest::parse("os = \"debian-8\"
+slices = [\"jekyll-3.0.0\", \"nginx\"]
+format = \"d\"
+outpath = \"Dockerfile\"
+sources = [\"https://example.com/slices-1.0.0.zip\"]
+").unwrap();
+        let os = manifest.os.unwrap();
+        assert_eq!((os.name.as_str(), os.version), ("debian", version::parse("8.0.0").unwrap()));
+        let slices = manifest.slices.iter().map(|s| (s.name.as_str(), s.version.to_string()))
+                                           .collect::<Vec<_>>();
+        assert_eq!(slices, vec![("jekyll", String::from("3.0.0")),
+                                ("nginx", String::from("0.0.0"))]);
+        assert_eq!(manifest.format, Some(String::from("d")));
+        assert_eq!(manifest.outpath, Some(String::from("Dockerfile")));
+        assert_eq!(manifest.sources, vec![String::from("https://example.com/slices-1.0.0.zip")]);
+    }
+
+    #[test]
+    fn empty_manifest_declares_nothing() {
+        let manifest = Manifest::parse("").unwrap();
+        assert!(manifest.os.is_none() && manifest.slices.is_empty());
+        assert!(manifest.sources.is_empty());
+    }
+
+    #[test]
+    fn parse_reports_position_of_syntax_error() {
+        match Manifest::parse("os = \"debian\"\nslices = = 1\nformat = \"d\"\n") {
+            Err(Error::Parse(ref message)) => assert!(message.contains("2:"), "{}", message),
+            _ => panic!("Parse error expected"),
+        }
+    }
+
+    #[test]
+    fn parse_rejects_unknown_key_and_wrong_type() {
+        assert!(Manifest::parse("layers = [\"jekyll\"]").is_err());
+        assert!(Manifest::parse("slices = \"jekyll\"").is_err());
+        assert!(Manifest::parse("format = 1").is_err());
+        assert!(Manifest::parse("slices = [\"\"]").is_err());
+    }
+}
@@ -1,99 +1,407 @@
-use std::env;
 use std::str::FromStr;
+use error::Error;
+use {ChecksumPolicy, DuplicateSlicePolicy, LockMode, PrereleasePolicy};
+use lock;
 
+/// How commands print their results
 #[derive(Copy)]
 #[derive(Clone)]
 #[derive(Debug)]
-pub enum Format {
-    Docker,
-    Shell,
+#[derive(PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
 }
 
-impl FromStr for Format {
+impl FromStr for OutputFormat {
     type Err = &'static str;
     fn from_str(str: &str) -> Result<Self, Self::Err> {
         match str {
-            "d" => Ok(Format::Docker),
-            "sh" => Ok(Format::Shell),
-            _ => Err("Unknown format. Available formats = [d, sh]")
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            _ => Err("Unknown output format. Available output formats = [text, json, yaml]")
         }
     }
 }
 
+#[derive(Clone)]
 #[derive(Debug)]
 pub struct Options {
-    pub format: Format,
+    /// Name of the formatter in `formatters::Registry`, e.g. `d` or `sh`
+    pub format: String,
     pub outpath: String,
     pub url: String,
+    pub duplicate_policy: DuplicateSlicePolicy,
+    pub prerelease_policy: PrereleasePolicy,
+    /// Container engine binary used by the `test` command, e.g. `docker`, `podman`, `buildah`
+    pub builder: String,
+    pub output: OutputFormat,
+    /// How the `fetch` command checks checksums of archives
+    pub checksum_policy: ChecksumPolicy,
+    /// Set by the `--lock` and `--locked` flags of the `make` command
+    pub lock_mode: LockMode,
+    pub lockfile: String,
+    /// Root directory given by `--root`, empty if it is to be discovered
+    pub root: String,
+    /// Bunch the `list` command is limited to, e.g. `slices-1.0.0` or `slices`, empty for all
+    pub bunch: String,
+    /// Glob the names of slices listed by the `list` command match, empty for all
+    pub name_pattern: String,
+    /// Whether the `list` command prints the dependency tree of each slice
+    pub tree: bool,
 }
 
 impl Options {
     pub fn new() -> Options {
-        Options { format: Format::Shell, outpath: String::new(), url: String::new() }
+        Options { format: String::from("sh"), outpath: String::new(), url: String::new(),
+                  duplicate_policy: DuplicateSlicePolicy::Deny,
+                  prerelease_policy: PrereleasePolicy::Exclude,
+                  builder: String::from("docker"), output: OutputFormat::Text,
+                  checksum_policy: ChecksumPolicy::Auto, lock_mode: LockMode::Ignore,
+                  lockfile: String::from(lock::DEFAULT_FILE_NAME), root: String::new(),
+                  bunch: String::new(), name_pattern: String::new(), tree: false }
     }
 
-    fn get_format_option_name() -> &'static str {
-        "f"
+    /// Sets the option which takes no value
+    fn set_flag(&mut self, long_name: &str) {
+        match long_name {
+            "lock" => self.lock_mode = LockMode::Write,
+            "locked" => self.lock_mode = LockMode::Locked,
+            "pre" => self.prerelease_policy = PrereleasePolicy::Include,
+            "tree" => self.tree = true,
+            _ => unreachable!("Flag --{} is not handled", long_name)
+        }
     }
 
-    fn get_outpath_option_name() -> &'static str {
-        "o"
+    fn set_option(&mut self, long_name: &str, value: String) -> Result<(), String> {
+        match long_name {
+            "format" => self.format = value,
+            "outpath" => self.outpath = value,
+            "url" => self.url = value,
+            "duplicates" => {
+                self.duplicate_policy = try!(DuplicateSlicePolicy::from_str(&value)
+                                                 .map_err(str::to_string))
+            }
+            "builder" => self.builder = value,
+            "output" => {
+                self.output = try!(OutputFormat::from_str(&value).map_err(str::to_string))
+            }
+            "verify" => {
+                self.checksum_policy = try!(ChecksumPolicy::from_str(&value)
+                                                .map_err(str::to_string))
+            }
+            "lockfile" => self.lockfile = value,
+            "root" => self.root = value,
+            "bunch" => self.bunch = value,
+            "name" => self.name_pattern = value,
+            _ => unreachable!("Option --{} is not handled", long_name)
+        }
+        Ok(())
     }
+}
 
-    fn get_url_option_name() -> &'static str {
-        "url"
-    }
+/// Option of the command line, either a flag or an option with a value
+pub struct OptionSpec {
+    pub long_name: &'static str,
+    pub short_name: Option<char>,
+    /// Placeholder of the value in the help, `None` for flags
+    pub value_name: Option<&'static str>,
+    pub help: &'static str,
+}
+
+/// Subcommand of the command line
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Positional arguments in the usage line
+    pub arguments: &'static str,
+    pub max_argument_count: usize,
+    pub summary: &'static str,
+    pub options: &'static [&'static OptionSpec],
+}
+
+const HELP: OptionSpec = OptionSpec { long_name: "help", short_name: Some('h'), value_name: None,
+                                      help: "Print help" };
+const VERSION: OptionSpec = OptionSpec { long_name: "version", short_name: Some('V'),
+                                         value_name: None, help: "Print the version" };
+const ROOT: OptionSpec = OptionSpec { long_name: "root", short_name: None,
+                                      value_name: Some("PATH"),
+                                      help: "Root directory of slices, see `sb env`" };
+const FORMAT: OptionSpec = OptionSpec { long_name: "format", short_name: Some('f'),
+                                        value_name: Some("FORMAT"),
+                                        help: "Formatter of the generated code: d, sh, make, \
+                                               template:PATH" };
+const OUTPATH: OptionSpec = OptionSpec { long_name: "outpath", short_name: Some('o'),
+                                         value_name: Some("PATH"),
+                                         help: "File the generated code is written to" };
+const URL: OptionSpec = OptionSpec { long_name: "url", short_name: None, value_name: Some("URL"),
+                                     help: "Source of slices: archive, directory or git url" };
+const VERIFY: OptionSpec = OptionSpec { long_name: "verify", short_name: None,
+                                        value_name: Some("POLICY"),
+                                        help: "Checksum policy: auto, require, skip" };
+const DUPLICATES: OptionSpec = OptionSpec { long_name: "duplicates", short_name: None,
+                                            value_name: Some("POLICY"),
+                                            help: "Policy for duplicate slices: deny, warn" };
+const PRE: OptionSpec = OptionSpec { long_name: "pre", short_name: None, value_name: None,
+                                     help: "Allow prerelease slices and bunches" };
+const BUILDER: OptionSpec = OptionSpec { long_name: "builder", short_name: None,
+                                         value_name: Some("ENGINE"),
+                                         help: "Container engine: docker, podman, buildah" };
+const OUTPUT: OptionSpec = OptionSpec { long_name: "output", short_name: None,
+                                        value_name: Some("FORMAT"),
+                                        help: "Format of the report: text, json, yaml" };
+const LOCK: OptionSpec = OptionSpec { long_name: "lock", short_name: None, value_name: None,
+                                      help: "Record the resolved slices in the lockfile" };
+const LOCKED: OptionSpec = OptionSpec { long_name: "locked", short_name: None, value_name: None,
+                                        help: "Use the slices recorded in the lockfile" };
+const LOCKFILE: OptionSpec = OptionSpec { long_name: "lockfile", short_name: None,
+                                          value_name: Some("PATH"),
+                                          help: "Lockfile path, sb.lock by default" };
+const BUNCH: OptionSpec = OptionSpec { long_name: "bunch", short_name: None,
+                                       value_name: Some("BUNCH"),
+                                       help: "Only slices of the bunch, e.g. slices-1.0.0" };
+const NAME: OptionSpec = OptionSpec { long_name: "name", short_name: None,
+                                      value_name: Some("GLOB"),
+                                      help: "Only slices whose name matches, e.g. 'ruby*'" };
+const TREE: OptionSpec = OptionSpec { long_name: "tree", short_name: None, value_name: None,
+                                      help: "Print the dependency tree of each slice" };
+
+/// Options accepted by every command
+static GLOBAL_OPTIONS: &'static [&'static OptionSpec] = &[&ROOT, &HELP, &VERSION];
 
-    fn set_option(&mut self, option_name: String, option_value: String) -> Result<(), String> {
-        let format_option_name = Options::get_format_option_name();
-        let outpath_option_name = Options::get_outpath_option_name();
-        let url_option_name = Options::get_url_option_name();
+static COMMANDS: &'static [CommandSpec] = &[
+    CommandSpec { name: "env", arguments: "", max_argument_count: 0,
+                  summary: "Print the directories and files sb uses",
+                  options: &[&LOCKFILE] },
+    CommandSpec { name: "fetch", arguments: "", max_argument_count: 0,
+                  summary: "Download slices into the root directory",
+                  options: &[&URL, &VERIFY] },
+    CommandSpec { name: "find", arguments: "[SLICES] [OS]", max_argument_count: 2,
+                  summary: "Search slices and their dependencies",
+                  options: &[&OUTPUT, &DUPLICATES, &PRE] },
+    CommandSpec { name: "list", arguments: "[OS]", max_argument_count: 1,
+                  summary: "List available slices by bunch",
+                  options: &[&BUNCH, &NAME, &TREE, &DUPLICATES] },
+    CommandSpec { name: "make", arguments: "[SLICES] [OS]", max_argument_count: 2,
+                  summary: "Generate code which installs slices",
+                  options: &[&FORMAT, &OUTPATH, &DUPLICATES, &PRE, &LOCK, &LOCKED,
+                            &LOCKFILE] },
+    CommandSpec { name: "test", arguments: "[SLICES] [OS]", max_argument_count: 2,
+                  summary: "Build the generated Dockerfile with a container engine",
+                  options: &[&BUILDER, &DUPLICATES, &PRE, &LOCK, &LOCKED, &LOCKFILE] },
+];
 
-        match option_name {
-            ref option_name if option_name == format_option_name => {
-                match Format::from_str(&option_value) {
-                    Ok(format) => self.format = format,
-                    Err(error) => return Err(error.to_string())
+/// What the command line asks for
+pub enum Invocation {
+    /// Run the command with the options and positional arguments
+    Command(&'static str, Options, Vec<String>),
+    /// Print the help text
+    Help(String),
+    /// Print the version
+    Version,
+}
+
+/// Parses `arguments` without the program name into `options`
+/// # Errors
+/// `Error::Usage` if a command, an option or a value is unknown or arguments are missing
+pub fn parse_arguments(arguments: Vec<String>, mut options: Options)
+                       -> Result<Invocation, Error> {
+    let mut command: Option<&'static CommandSpec> = None;
+    let mut positional_arguments = Vec::new();
+    let mut arguments = arguments.into_iter();
+    let mut options_ended = false;
+    while let Some(argument) = arguments.next() {
+        if options_ended || argument == "-" || !argument.starts_with('-') {
+            match command {
+                None if argument == "help" => return Ok(Invocation::Help(help(arguments.next()))),
+                None => command = Some(try!(find_command(&argument))),
+                Some(_) => positional_arguments.push(argument),
+            }
+            continue;
+        }
+        if argument == "--" {
+            options_ended = true;
+            continue;
+        }
+        let (option, value) = try!(find_option(&argument, command));
+        match (option.value_name, value) {
+            (None, Some(_)) => {
+                return Err(usage_error(format!("Option --{} takes no value", option.long_name),
+                                       command));
+            }
+            (None, None) => {
+                match option.long_name {
+                    "help" => {
+                        let command_name = command.map(|c| c.name.to_string());
+                        return Ok(Invocation::Help(help(command_name)));
+                    }
+                    "version" => return Ok(Invocation::Version),
+                    long_name => options.set_flag(long_name),
                 }
             }
-            ref option_name if option_name == outpath_option_name => self.outpath = option_value,
-            ref option_name if option_name == url_option_name => self.url = option_value,
-            _ => return Err(format!("Unknown option = {}", option_name))
+            (Some(value_name), value) => {
+                let value = try!(value.or_else(|| arguments.next()).ok_or_else(|| {
+                    usage_error(format!("Option --{} expects a value {}", option.long_name,
+                                        value_name),
+                                command)
+                }));
+                try!(options.set_option(option.long_name, value)
+                            .map_err(|message| usage_error(message, command)));
+            }
         }
-        Ok(())
+    }
+    let command = try!(command.ok_or_else(|| {
+        Error::Usage(format!("Command expected\n\n{}", general_help().trim_right()))
+    }));
+    if positional_arguments.len() > command.max_argument_count {
+        return Err(usage_error(format!("Unexpected argument = {}",
+                                       positional_arguments[command.max_argument_count]),
+                               Some(command)));
+    }
+    Ok(Invocation::Command(command.name, options, positional_arguments))
+}
+
+/// Returns the help of the command, or the general help if `command` is `None` or unknown
+pub fn help(command: Option<String>) -> String {
+    match command.and_then(|name| COMMANDS.iter().find(|c| c.name == name)) {
+        Some(command) => command_help(command),
+        None => general_help(),
+    }
+}
+
+fn find_command(name: &str) -> Result<&'static CommandSpec, Error> {
+    COMMANDS.iter().find(|c| c.name == name).ok_or_else(|| {
+        usage_error(format!("Unknown command \"{}\"", name), None)
+    })
+}
+
+/// Finds the option of `--name`, `--name=value`, `-n` or `-nvalue` among the global options
+/// and the options of `command`
+fn find_option(argument: &str, command: Option<&'static CommandSpec>)
+               -> Result<(&'static OptionSpec, Option<String>), Error> {
+    let command_options = command.map(|c| c.options).unwrap_or(&[]);
+    let mut all_options = GLOBAL_OPTIONS.iter().chain(command_options.iter());
+    let (option, value) = if argument.starts_with("--") {
+        let mut parts = argument[2..].splitn(2, '=');
+        let name = parts.next().unwrap();
+        (all_options.find(|o| o.long_name == name), parts.next().map(str::to_string))
+    } else {
+        let mut chars = argument[1..].chars();
+        let name = chars.next();
+        let value = chars.as_str();
+        let value = if value.is_empty() { None } else { Some(value.to_string()) };
+        (all_options.find(|o| o.short_name.is_some() && o.short_name == name), value)
+    };
+    match option {
+        Some(option) => Ok((option, value)),
+        None => {
+            let message = match command {
+                Some(command) => format!("Unknown option {} for command {}", argument,
+                                         command.name),
+                None => format!("Unknown option {}", argument),
+            };
+            Err(usage_error(message, command))
+        }
+    }
+}
+
+fn usage_error(message: String, command: Option<&CommandSpec>) -> Error {
+    let help_command = match command {
+        Some(command) => format!("sb help {}", command.name),
+        None => String::from("sb help"),
+    };
+    Error::Usage(format!("{}. Run `{}` for usage", message, help_command))
+}
+
+fn general_help() -> String {
+    let mut help = String::from("Usage: sb [OPTIONS] COMMAND [ARGUMENTS]\n\nCommands:\n");
+    for command in COMMANDS {
+        help.push_str(&format!("  {:<8}{}\n", command.name, command.summary));
+    }
+    help.push_str("\nOptions:\n");
+    push_options_help(&mut help, GLOBAL_OPTIONS);
+    help.push_str("\nRun `sb help COMMAND` for the options of a command.\n");
+    help
+}
+
+fn command_help(command: &CommandSpec) -> String {
+    let mut help = format!("Usage: sb {} [OPTIONS]", command.name);
+    if !command.arguments.is_empty() {
+        help.push_str(&format!(" {}", command.arguments));
+    }
+    help.push_str(&format!("\n\n{}\n\nOptions:\n", command.summary));
+    push_options_help(&mut help, command.options);
+    push_options_help(&mut help, GLOBAL_OPTIONS);
+    help
+}
+
+fn push_options_help(help: &mut String, options: &[&OptionSpec]) {
+    for option in options {
+        let short_name = option.short_name.map(|c| format!("-{},", c)).unwrap_or_default();
+        let value_name = option.value_name.map(|v| format!(" {}", v)).unwrap_or_default();
+        let name = format!("{:<4}--{}{}", short_name, option.long_name, value_name);
+        help.push_str(&format!("  {:<26}{}\n", name, option.help));
     }
 }
 
-pub fn parse_options() -> (String, Options, Vec<String>) {
-    let mut options = Options::new();
-    let mut args = env::args();
-    let app_path = args.next().unwrap();
+#[cfg(test)]
+mod tests {
+    use LockMode;
+    use error::Error;
+    use super::{help, parse_arguments, Invocation, Options};
+
+    fn parse(arguments: &[&str]) -> Result<Invocation, Error> {
+        parse_arguments(arguments.iter().map(|a| a.to_string()).collect(), Options::new())
+    }
 
-    let mut current_option_name: Option<String> = Option::None;
-    let mut remaining_arguments: Vec<String> = Vec::new();
-    for argument in args {
-        if let Some(option_name) = current_option_name {
-            let result = options.set_option(option_name.to_string(), argument);
-            if let Err(error) = result {
-                panic!("{}", error);
+    #[test]
+    fn parse_command_with_short_and_long_options() {
+        let arguments = ["--root", "/sb", "make", "-f", "d", "--outpath=out", "--lock", "jekyll"];
+        match parse(&arguments).unwrap() {
+            Invocation::Command(command, options, arguments) => {
+                assert_eq!(command, "make");
+                assert_eq!((options.root.as_str(), options.outpath.as_str()), ("/sb", "out"));
+                assert_eq!(options.format, "d");
+                assert_eq!(options.lock_mode, LockMode::Write);
+                assert_eq!(arguments, vec![String::from("jekyll")]);
             }
-            current_option_name = Option::None
-        } else if argument.starts_with('-') {
-            let argument_without_dashes = argument.trim_left_matches('-');
-            if argument_without_dashes.contains('=') {
-                let parts: Vec<&str> = argument_without_dashes.splitn(2, '=').collect();
-                let option_name = parts.first().unwrap();
-                let option_value = parts.last().unwrap();
-                let result = options.set_option(option_name.to_string(), option_value.to_string());
-                if let Err(error) = result {
-                    panic!("{}", error);
-                }
-            } else {
-                current_option_name = Some(argument_without_dashes.to_string());
+            _ => panic!("Command expected"),
+        }
+    }
+
+    #[test]
+    fn help_and_version_are_not_errors() {
+        match parse(&["make", "--help"]).unwrap() {
+            Invocation::Help(text) => assert!(text.starts_with("Usage: sb make")),
+            _ => panic!("Help expected"),
+        }
+        match parse(&["help", "fetch"]).unwrap() {
+            Invocation::Help(text) => assert!(text.contains("--url URL")),
+            _ => panic!("Help expected"),
+        }
+        assert!(match parse(&["-V"]).unwrap() { Invocation::Version => true, _ => false });
+    }
+
+    #[test]
+    fn usage_errors() {
+        let invalid_arguments: &[&[&str]] = &[&[], &["build"], &["make", "--url", "x"],
+                                              &["make", "-f"], &["make", "--lock=yes"],
+                                              &["fetch", "jekyll"],
+                                              &["make", "jekyll", "debian", "extra"]];
+        for arguments in invalid_arguments {
+            match parse(arguments) {
+                Err(Error::Usage(_)) => {}
+                _ => panic!("Usage error expected for {:?}", arguments),
             }
-        } else {
-            remaining_arguments.push(argument)
         }
     }
-    (app_path, options, remaining_arguments)
+
+    #[test]
+    fn help_lists_every_command() {
+        let text = help(None);
+        for command in &["env", "fetch", "find", "list", "make", "test"] {
+            assert!(text.contains(&format!("  {} ", command)), "{}", text);
+        }
+    }
 }
@@ -1,5 +1,8 @@
-use semver::Version;
+use VersionMatchStrategy;
+use error::Result;
+use version::{self, SliceVersion};
 
+/// Requested OS
 #[derive(Clone)]
 #[derive(Debug)]
 #[derive(PartialEq)]
@@ -7,5 +10,16 @@ use semver::Version;
 #[derive(Eq)]
 pub struct Os {
     pub name: String,
-	pub version: Version,
+	pub version: SliceVersion,
+	pub version_match_strategy: VersionMatchStrategy,
+}
+
+impl Os {
+    /// Parses a request like `debian^8` in the syntax of requested slices
+    /// # Errors
+    /// `Error::Parse` if the request is invalid
+    pub fn parse(request: &str) -> Result<Os> {
+        let (name, version, strategy) = try!(version::parse_request(request));
+        Ok(Os { name: name, version: version, version_match_strategy: strategy })
+    }
 }
@@ -0,0 +1,180 @@
+//! Install plan: the slices `make` generates code for, in the order they are installed
+//!
+//! The plan starts with the OS slice. Every other slice follows its dependencies and appears
+//! once, however many requested slices depend on it. Formatters generate code from the plan.
+
+use std::path::PathBuf;
+use error::{Error, Result};
+use slice::{Section, Slice};
+use version::SliceVersion;
+
+/// Why a slice is in the plan
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Reason {
+    /// Base layer of the requested OS
+    Os,
+    Requested,
+    /// Dependency of a requested slice
+    Dependency,
+}
+
+/// Slice of the plan
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Step {
+    pub name: String,
+    pub version: SliceVersion,
+    /// Name of the bunch with its version, e.g. `slices-du-0.0.2`
+    pub bunch: String,
+    /// Path of the slice file
+    pub path: PathBuf,
+    pub reason: Reason,
+    /// Full names of the slices the slice depends on by `DEP`, e.g. `wget-0.0.0`
+    pub dependencies: Vec<String>,
+    /// Sections of the slice file in their original order, except `DEP`
+    pub sections: Vec<Section>,
+}
+
+impl Step {
+    /// Binds every `DEP` entry of `slice` to the step with the same name among `planned`
+    /// steps, so the dependencies are the versions which are actually installed
+    /// # Errors
+    /// `Error::Resolution` if no step of `planned` has the name of a dependency
+    fn from_slice(slice: &Slice, reason: Reason, planned: &[Step]) -> Result<Step> {
+        let mut dependencies = Vec::new();
+        for dependency in slice.dependencies() {
+            let step = planned.iter().find(|step| step.name == *dependency.name());
+            let step = try!(step.ok_or_else(|| {
+                Error::Resolution(format!("{}-{} depends on {}, but it is not planned",
+                                          slice.name(), slice.version(), dependency))
+            }));
+            let full_name = step.full_name();
+            if !dependencies.contains(&full_name) {
+                dependencies.push(full_name);
+            }
+        }
+        Ok(Step {
+            name: slice.name().clone(),
+            version: slice.version().clone(),
+            bunch: slice.bunch().to_string(),
+            path: slice.path().to_path_buf(),
+            reason: reason,
+            dependencies: dependencies,
+            sections: slice.sections().clone(),
+        })
+    }
+
+    /// Name with the version, e.g. `ruby-2.2.3`
+    pub fn full_name(&self) -> String {
+        format!("{}-{}", self.name, self.version)
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Plan {
+    steps: Vec<Step>,
+}
+
+impl Plan {
+    /// Creates the plan from `os_slice` and `slices`, which are expected to be in dependency
+    /// order. A slice repeated in `slices` is kept at its first position only.
+    /// # Arguments
+    /// * `requested_names` names of the requested slices, the other slices are dependencies
+    /// # Errors
+    /// `Error::Resolution` if a dependency of a slice does not precede it in `slices`
+    pub fn new(os_slice: &Slice, slices: &[&Slice], requested_names: &[&str]) -> Result<Plan> {
+        let mut steps = vec![try!(Step::from_slice(os_slice, Reason::Os, &[]))];
+        for slice in slices {
+            if steps.iter().any(|step| step.path == slice.path()) {
+                continue;
+            }
+            let reason = if requested_names.contains(&slice.name().as_str()) {
+                Reason::Requested
+            } else {
+                Reason::Dependency
+            };
+            let step = try!(Step::from_slice(slice, reason, &steps));
+            steps.push(step);
+        }
+        Ok(Plan { steps: steps })
+    }
+
+    /// Creates the plan from steps which are already in the install order, e.g. to check the
+    /// output of a formatter
+    pub fn from_steps(steps: Vec<Step>) -> Plan {
+        Plan { steps: steps }
+    }
+
+    /// All steps in the install order, the OS step first
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Steps of the requested slices
+    pub fn requested_steps(&self) -> Vec<&Step> {
+        self.steps.iter().filter(|step| step.reason == Reason::Requested).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+    use {DuplicateSlicePolicy, PrereleasePolicy, VersionMatchStrategy};
+    use os::Os;
+    use slice::{Bunch, List, Slice};
+    use version;
+    use super::{Plan, Reason};
+
+    fn create_slice(name: &str) -> Slice {
+        let bunch = Bunch::new(String::from("slices"), version::parse("1.0.0").unwrap());
+        Slice::new(String::from(name), version::zero(), bunch, PathBuf::from(name), Vec::new(),
+                   Vec::new(), Vec::new(), Vec::new())
+    }
+
+    #[test]
+    fn shared_dependency_is_planned_once() {
+        let (debian, update) = (create_slice("debian"), create_slice("update"));
+        let (jekyll, mysql) = (create_slice("jekyll"), create_slice("mysql-server"));
+        let plan = Plan::new(&debian, &[&update, &jekyll, &update, &mysql],
+                             &["jekyll", "mysql-server"]).unwrap();
+        let steps = plan.steps().iter().map(|s| (s.full_name(), s.reason)).collect::<Vec<_>>();
+        assert_eq!(steps, vec![(String::from("debian-0.0.0"), Reason::Os),
+                               (String::from("update-0.0.0"), Reason::Dependency),
+                               (String::from("jekyll-0.0.0"), Reason::Requested),
+                               (String::from("mysql-server-0.0.0"), Reason::Requested)]);
+        assert_eq!(plan.requested_steps().len(), 2);
+        assert_eq!(plan.steps()[1].bunch, "slices-1.0.0");
+    }
+
+    #[test]
+    fn steps_of_test_slices_have_resolved_dependencies() {
+        let path = env::current_dir().expect("Current dir is not set").join("test_slices");
+        let os = Os::parse("debian").unwrap();
+        let list = List::new(&path, &os, DuplicateSlicePolicy::Deny, PrereleasePolicy::Include)
+                       .unwrap();
+        let strategy = VersionMatchStrategy::ExactOrGreater;
+        let jekyll = list.find_slice("jekyll", &version::zero(), strategy).unwrap();
+        let bc = list.find_slice("bc", &version::zero(), strategy).unwrap();
+        let slices = list.dependency_order(&[jekyll, bc]).unwrap();
+        let plan = Plan::new(list.os_slice().unwrap(), &slices, &["jekyll", "bc"]).unwrap();
+        let steps = plan.steps()
+                        .iter()
+                        .map(|s| (s.full_name(), s.dependencies.clone()))
+                        .collect::<Vec<_>>();
+        let strings = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(steps, vec![(String::from("debian-8.2.0"), Vec::new()),
+                               (String::from("update-0.0.0"), Vec::new()),
+                               (String::from("wget-0.0.0"), strings(&["update-0.0.0"])),
+                               (String::from("ruby-2.2.3"), strings(&["wget-0.0.0"])),
+                               (String::from("jekyll-3.0.0-beta.9"), strings(&["ruby-2.2.3"])),
+                               (String::from("bc-0.0.0"), strings(&["update-0.0.0"]))]);
+    }
+
+}
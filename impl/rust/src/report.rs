@@ -0,0 +1,198 @@
+//! Machine-readable results of commands
+
+use std::collections::BTreeMap;
+use rustc_serialize::json::{Json, ToJson};
+
+/// Slice as reported to users
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct SliceEntry {
+    pub name: String,
+    pub version: String,
+    /// Name of the bunch with its version, e.g. `slices-du-0.0.2`
+    pub bunch: String,
+    /// Path of the slice file
+    pub path: String,
+}
+
+/// Requested slice which is not found
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct RequestedEntry {
+    pub name: String,
+    pub version: String,
+}
+
+/// Entry of a `DEP` section which no slice satisfies
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct UnresolvedDependency {
+    /// The entry as written in the `DEP` section
+    pub dependency: String,
+    /// Slice which declares the dependency
+    pub slice: SliceEntry,
+}
+
+/// Result of the `find` command
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct FindReport {
+    pub found: Vec<SliceEntry>,
+    pub similar: Vec<SliceEntry>,
+    pub missing_requested: Vec<RequestedEntry>,
+    /// Prereleases which match a request but are not selected without `--pre`
+    pub skipped_prereleases: Vec<SliceEntry>,
+    pub unresolved_dependencies: Vec<UnresolvedDependency>,
+}
+
+impl FindReport {
+    pub fn to_json(&self) -> String {
+        format!("{}\n", ToJson::to_json(self).pretty())
+    }
+
+    pub fn to_yaml(&self) -> String {
+        let mut yaml = String::new();
+        push_yaml_list(&mut yaml, "found", &self.found, |yaml, entry| {
+            push_yaml_slice(yaml, "  - ", "    ", entry)
+        });
+        push_yaml_list(&mut yaml, "similar", &self.similar, |yaml, entry| {
+            push_yaml_slice(yaml, "  - ", "    ", entry)
+        });
+        push_yaml_list(&mut yaml, "missing_requested", &self.missing_requested, |yaml, entry| {
+            yaml.push_str(&format!("  - name: {}\n", quote(&entry.name)));
+            yaml.push_str(&format!("    version: {}\n", quote(&entry.version)));
+        });
+        push_yaml_list(&mut yaml, "skipped_prereleases", &self.skipped_prereleases,
+                       |yaml, entry| push_yaml_slice(yaml, "  - ", "    ", entry));
+        push_yaml_list(&mut yaml, "unresolved_dependencies", &self.unresolved_dependencies,
+                       |yaml, entry| {
+            yaml.push_str(&format!("  - dependency: {}\n", quote(&entry.dependency)));
+            yaml.push_str("    slice:\n");
+            push_yaml_slice(yaml, "      ", "      ", &entry.slice);
+        });
+        yaml
+    }
+}
+
+impl ToJson for SliceEntry {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("name"), self.name.to_json());
+        object.insert(String::from("version"), self.version.to_json());
+        object.insert(String::from("bunch"), self.bunch.to_json());
+        object.insert(String::from("path"), self.path.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for RequestedEntry {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("name"), self.name.to_json());
+        object.insert(String::from("version"), self.version.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for UnresolvedDependency {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("dependency"), self.dependency.to_json());
+        object.insert(String::from("slice"), self.slice.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for FindReport {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("found"), self.found.to_json());
+        object.insert(String::from("similar"), self.similar.to_json());
+        object.insert(String::from("missing_requested"), self.missing_requested.to_json());
+        object.insert(String::from("skipped_prereleases"), self.skipped_prereleases.to_json());
+        object.insert(String::from("unresolved_dependencies"),
+                      self.unresolved_dependencies.to_json());
+        Json::Object(object)
+    }
+}
+
+fn push_yaml_list<T, F: Fn(&mut String, &T)>(yaml: &mut String, key: &str, items: &[T],
+                                              push_item: F) {
+    if items.is_empty() {
+        return yaml.push_str(&format!("{}: []\n", key));
+    }
+    yaml.push_str(&format!("{}:\n", key));
+    for item in items {
+        push_item(yaml, item);
+    }
+}
+
+/// `first_indent` starts the first field, `indent` starts the others
+fn push_yaml_slice(yaml: &mut String, first_indent: &str, indent: &str, entry: &SliceEntry) {
+    yaml.push_str(&format!("{}name: {}\n", first_indent, quote(&entry.name)));
+    yaml.push_str(&format!("{}version: {}\n", indent, quote(&entry.version)));
+    yaml.push_str(&format!("{}bunch: {}\n", indent, quote(&entry.bunch)));
+    yaml.push_str(&format!("{}path: {}\n", indent, quote(&entry.path)));
+}
+
+/// JSON strings are valid double-quoted YAML scalars
+fn quote(string: &str) -> String {
+    Json::String(string.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_serialize::json::Json;
+    use super::{FindReport, RequestedEntry, SliceEntry, UnresolvedDependency};
+
+    fn create_report() -> FindReport {
+        let slice = SliceEntry { name: String::from("jekyll"), version: String::from("3.0.0"),
+                                 bunch: String::from("slices-0.0.2"),
+                                 path: String::from("/sb/j/jekyll-3") };
+        let dependency = UnresolvedDependency { dependency: String::from("ruby \"2\""),
+                                                slice: slice.clone() };
+        FindReport { found: vec![slice], similar: Vec::new(),
+                     missing_requested: vec![RequestedEntry { name: String::from("nginx"),
+                                                              version: String::from("0.0.0") }],
+                     skipped_prereleases: Vec::new(), unresolved_dependencies: vec![dependency] }
+    }
+
+    #[test]
+    fn json_contains_all_entries() {
+        let json = Json::from_str(&create_report().to_json()).unwrap();
+        let found = json.find("found").and_then(Json::as_array).unwrap();
+        assert_eq!(found[0].find("bunch").and_then(Json::as_string), Some("slices-0.0.2"));
+        let similar = json.find("similar").and_then(Json::as_array).unwrap();
+        assert!(similar.is_empty());
+        let dependency = json.find_path(&["unresolved_dependencies"])
+                             .and_then(Json::as_array).unwrap();
+        assert_eq!(dependency[0].find("dependency").and_then(Json::as_string),
+                   Some("ruby \"2\""));
+    }
+
+    #[test]
+    fn yaml_contains_all_entries() {
+        assert_eq!(create_report().to_yaml(), r#"found:
+  - name: "jekyll"
+    version: "3.0.0"
+    bunch: "slices-0.0.2"
+    path: "/sb/j/jekyll-3"
+similar: []
+missing_requested:
+  - name: "nginx"
+    version: "0.0.0"
+skipped_prereleases: []
+unresolved_dependencies:
+  - dependency: "ruby \"2\""
+    slice:
+      name: "jekyll"
+      version: "3.0.0"
+      bunch: "slices-0.0.2"
+      path: "/sb/j/jekyll-3"
+"#);
+    }
+}
@@ -0,0 +1,174 @@
+//! Discovery of the root directory which holds slices, the trust store and generated files
+//!
+//! The root directory is taken from the first of:
+//! 1. the `--root` option
+//! 2. the `SB_HOME` environment variable
+//! 3. a `.sb` directory in the current directory or one of its ancestors
+//! 4. `sb` in the XDG user data directory, `$XDG_DATA_HOME` or `$HOME/.local/share`
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::path::{Path, PathBuf};
+use error::{Error, Result};
+
+/// Environment variable which selects the root directory
+pub const HOME_VARIABLE: &'static str = "SB_HOME";
+/// Name of the project-local root directory
+pub const PROJECT_DIRECTORY_NAME: &'static str = ".sb";
+
+/// Where the root directory is taken from
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum RootOrigin {
+    Option,
+    Environment,
+    Project,
+    UserData,
+}
+
+impl Display for RootOrigin {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        let origin = match *self {
+            RootOrigin::Option => "--root option",
+            RootOrigin::Environment => HOME_VARIABLE,
+            RootOrigin::Project => "project directory",
+            RootOrigin::UserData => "user data directory",
+        };
+        formatter.write_str(origin)
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Root {
+    /// Absolute path of the root directory
+    pub directory: PathBuf,
+    pub origin: RootOrigin,
+}
+
+impl Root {
+    /// Directory of the slice bunches
+    pub fn slice_root_directory(&self) -> PathBuf {
+        self.directory.join("slices")
+    }
+}
+
+/// Values the root directory is discovered from
+pub struct Environment {
+    pub root_option: Option<PathBuf>,
+    pub sb_home: Option<OsString>,
+    pub current_directory: PathBuf,
+    pub xdg_data_home: Option<OsString>,
+    pub home: Option<OsString>,
+}
+
+impl Environment {
+    /// Takes the environment of the process, `root_option` is the value of `--root`, if given
+    /// # Errors
+    /// `Error::Io` if the current directory is not available
+    pub fn current(root_option: Option<PathBuf>) -> Result<Environment> {
+        let current_directory = try!(env::current_dir().map_err(|e| Error::io(".", e)));
+        Ok(Environment {
+            root_option: root_option,
+            sb_home: env::var_os(HOME_VARIABLE),
+            current_directory: current_directory,
+            xdg_data_home: env::var_os("XDG_DATA_HOME"),
+            home: env::var_os("HOME"),
+        })
+    }
+}
+
+/// # Errors
+/// `Error::Usage` if no root directory can be found, i.e. there is no project directory and
+/// neither `XDG_DATA_HOME` nor `HOME` is set
+pub fn discover(environment: &Environment) -> Result<Root> {
+    let absolute = |path: &Path| environment.current_directory.join(path);
+    if let Some(ref path) = environment.root_option {
+        return Ok(Root { directory: absolute(path), origin: RootOrigin::Option });
+    }
+    if let Some(ref path) = non_empty(&environment.sb_home) {
+        return Ok(Root { directory: absolute(Path::new(path)), origin: RootOrigin::Environment });
+    }
+    let mut directory = Some(environment.current_directory.as_path());
+    while let Some(current_directory) = directory {
+        let path = current_directory.join(PROJECT_DIRECTORY_NAME);
+        if path.is_dir() {
+            return Ok(Root { directory: path, origin: RootOrigin::Project });
+        }
+        directory = current_directory.parent();
+    }
+    let data_home = non_empty(&environment.xdg_data_home).map(PathBuf::from).or_else(|| {
+        non_empty(&environment.home).map(|home| Path::new(home).join(".local").join("share"))
+    });
+    match data_home {
+        Some(data_home) => {
+            Ok(Root { directory: absolute(&data_home.join("sb")), origin: RootOrigin::UserData })
+        }
+        None => {
+            Err(Error::Usage(format!("Root directory is not found. Use the --root option or the \
+                                      {} environment variable",
+                                     HOME_VARIABLE)))
+        }
+    }
+}
+
+fn non_empty(value: &Option<OsString>) -> Option<&OsString> {
+    value.as_ref().and_then(|value| if value.is_empty() { None } else { Some(value) })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::PathBuf;
+    use helper;
+    use super::{discover, Environment, RootOrigin};
+
+    fn environment(current_directory: PathBuf) -> Environment {
+        Environment { root_option: None, sb_home: None, current_directory: current_directory,
+                      xdg_data_home: Some(OsString::from("/data")),
+                      home: Some(OsString::from("/home/user")) }
+    }
+
+    #[test]
+    fn option_takes_precedence_over_environment() {
+        let mut environment = environment(PathBuf::from("/work"));
+        environment.root_option = Some(PathBuf::from("roots/sb"));
+        environment.sb_home = Some(OsString::from("/sb"));
+        let root = discover(&environment).unwrap();
+        assert_eq!((root.directory, root.origin),
+                   (PathBuf::from("/work/roots/sb"), RootOrigin::Option));
+        environment.root_option = None;
+        let root = discover(&environment).unwrap();
+        assert_eq!((root.directory, root.origin), (PathBuf::from("/sb"), RootOrigin::Environment));
+    }
+
+    #[test]
+    fn project_directory_is_found_in_ancestors() {
+        let project = helper::create_temporary_directory("sb-project").unwrap();
+        let nested = project.join("src").join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(project.join(".sb")).unwrap();
+        let root = discover(&environment(nested)).unwrap();
+        assert_eq!((root.directory, root.origin), (project.join(".sb"), RootOrigin::Project));
+        fs::remove_dir_all(&project).unwrap();
+    }
+
+    #[test]
+    fn user_data_directory_is_the_fallback() {
+        let directory = helper::create_temporary_directory("sb-no-project").unwrap();
+        let mut environment = environment(directory.clone());
+        let root = discover(&environment).unwrap();
+        assert_eq!((root.directory, root.origin),
+                   (PathBuf::from("/data/sb"), RootOrigin::UserData));
+        environment.xdg_data_home = Some(OsString::new());
+        let root = discover(&environment).unwrap();
+        assert_eq!(root.directory, PathBuf::from("/home/user/.local/share/sb"));
+        environment.home = None;
+        assert!(discover(&environment).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
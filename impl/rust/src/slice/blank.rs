@@ -0,0 +1,111 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt::{Debug, Formatter, Result as FormatResult};
+use std::path::PathBuf;
+use error::{Error, Result};
+use slice::bunch::Bunch;
+use slice::dependency::Dependency;
+use slice::section::{Kind, Section};
+use version::SliceVersion;
+
+/// Slice read from a file whose dependencies are not resolved yet
+pub struct SliceBlank {
+    pub name: String,
+	pub version: SliceVersion,
+	pub bunch: Bunch,
+	pub path: PathBuf,
+	pub dependencies: Vec<Dependency>,
+	pub sections: Vec<Section>,
+}
+
+impl SliceBlank {
+    pub fn from_lines<L:Borrow<str>+Debug>(name: String, version: SliceVersion, bunch: Bunch,
+	                                       path: PathBuf, mut lines: Vec<L>)
+	                                       -> Result<SliceBlank> {
+		let mut slice_blank = SliceBlank { name: name, version: version, bunch: bunch,
+		                                   path: path, dependencies: Vec::new(),
+		                                   sections: Vec::new() };
+        while !lines.is_empty() {
+            let (section, remaining_lines) = Section::from_lines(lines);
+            lines = remaining_lines;
+            if let Some(section) = section {
+                match section.kind {
+                    Kind::Dep => {
+                        let mut dependencies = Vec::new();
+                        for item in &section.items {
+                            let dependency = try!(Dependency::parse(item).map_err(|error| {
+                                match error {
+                                    Error::Parse(message) => {
+                                        Error::Parse(format!("{}. Path = {}", message,
+                                                             slice_blank.path.display()))
+                                    }
+                                    error => error,
+                                }
+                            }));
+                            dependencies.push(dependency);
+                        }
+                        slice_blank.dependencies = dependencies;
+                    }
+                    _ => slice_blank.sections.push(section)
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(slice_blank)
+	}
+}
+
+impl PartialEq for SliceBlank {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.version == other.version &&
+        self.bunch.version == other.bunch.version
+    }
+}
+
+impl PartialOrd for SliceBlank {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Eq for SliceBlank {
+}
+
+/// Blanks are ordered the same way as slices: by bunch version, then by version, then by name
+impl Ord for SliceBlank {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bunch.version.cmp(&other.bunch.version)
+                          .then(self.version.cmp(&other.version))
+                          .then(self.name.cmp(&other.name))
+    }
+}
+
+impl Debug for SliceBlank {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        write!(formatter, "SliceBlank({})", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use error::Error;
+    use slice::bunch::Bunch;
+    use version;
+    use super::SliceBlank;
+
+    #[test]
+    fn empty_dependency_is_reported_with_path() {
+        let bunch = Bunch::new(String::from("slices"), version::zero());
+        let lines = vec!["DEP", "wget", " ", "RUN", "make"];
+        let result = SliceBlank::from_lines(String::from("ruby"), version::zero(), bunch,
+                                            PathBuf::from("/slices/r/ruby"), lines);
+        match result {
+            Err(Error::Parse(ref message)) => {
+                assert_eq!(message, "Empty dependency. Path = /slices/r/ruby")
+            }
+            result => panic!("Unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use error::Result;
+use version::{self, SliceVersion};
+
+/// Directory of slices with a semver name, e.g. `slices-1.0.0`
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+pub struct Bunch {
+    pub name: String,
+    pub version: SliceVersion,
+}
+
+impl Bunch {
+    pub fn new(name: String, version: SliceVersion) -> Bunch {
+        Bunch { name: name, version: version }
+    }
+
+    pub fn from_directory_name(directory_name: &str) -> Result<Bunch> {
+        let (name, version) = try!(version::extract_name_and_version(directory_name));
+        Ok(Bunch::new(name, version))
+    }
+
+    /// Checks the rule that the tool uses only bunches with the same major version
+    pub fn is_compatible_with(&self, tool_version: &SliceVersion) -> bool {
+        self.version.major == tool_version.major
+    }
+}
+
+impl Display for Bunch {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        write!(formatter, "{}-{}", self.name, self.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use version;
+    use super::Bunch;
+
+    #[test]
+    fn from_directory_name() {
+        let bunch = Bunch::from_directory_name("slices-du-0.0.2").unwrap();
+        assert_eq!(bunch.name, "slices-du");
+        assert_eq!(bunch.version, version::parse("0.0.2").unwrap());
+    }
+
+    #[test]
+    fn bunch_is_compatible_only_with_same_major() {
+        let bunch = Bunch::from_directory_name("slices-2.1.1").unwrap();
+        assert!(bunch.is_compatible_with(&version::parse("2.0.0").unwrap()));
+        assert!(!bunch.is_compatible_with(&version::parse("1.9.0").unwrap()));
+        assert!(!bunch.is_compatible_with(&version::parse("3.0.0").unwrap()));
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use semver::VersionReq;
+use error::{Error, Result};
+use version::{self, SliceVersion};
+
+/// Entry of a `DEP` section: a slice name with an optional version requirement
+///
+/// Supported forms:
+/// * `ruby` any version
+/// * `ruby-2.2` version 2.2.0 or greater
+/// * `ruby >=2.1 <3`, `ruby ~2.2.3`, `ruby ^2` semver requirements separated by spaces
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Dependency {
+    name: String,
+    requirement: VersionReq,
+    text: String,
+}
+
+impl Dependency {
+    /// # Errors
+    /// `Error::Parse` if the text is empty or has an invalid version or requirement
+    pub fn parse(text: &str) -> Result<Dependency> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(Error::Parse(String::from("Empty dependency")));
+        }
+        let mut parts = text.split_whitespace();
+        let first_part = parts.next().unwrap();
+        let (name, version) = try!(version::extract_name_and_version(first_part));
+        let mut predicates = Vec::new();
+        if version != version::zero() {
+            predicates.push(format!(">={}.{}.{}", version.major, version.minor, version.patch));
+        }
+        predicates.append(&mut group_predicates(parts));
+        let requirement = if predicates.is_empty() {
+            VersionReq::any()
+        } else {
+            try!(parse_requirement(text, &predicates.join(", ")))
+        };
+        Ok(Dependency { name: name, requirement: requirement, text: text.to_string() })
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn requirement(&self) -> &VersionReq {
+        &self.requirement
+    }
+
+    pub fn matches(&self, version: &SliceVersion) -> bool {
+        self.requirement.matches(&version.to_semver())
+    }
+}
+
+impl Display for Dependency {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        write!(formatter, "{}", self.text)
+    }
+}
+
+/// Joins a sigil separated from its version by a space, e.g. `>= 2.1`, with the version
+fn group_predicates<'a, I: Iterator<Item=&'a str>>(parts: I) -> Vec<String> {
+    let mut predicates: Vec<String> = Vec::new();
+    for part in parts {
+        let previous_is_sigil = predicates.last().map_or(false, |p| is_sigil(p));
+        if previous_is_sigil {
+            predicates.last_mut().unwrap().push_str(part);
+        } else {
+            predicates.push(part.to_string());
+        }
+    }
+    predicates
+}
+
+fn is_sigil(string: &str) -> bool {
+    string.chars().all(|c| "<>=~^".contains(c))
+}
+
+fn parse_requirement(text: &str, requirement: &str) -> Result<VersionReq> {
+    VersionReq::parse(requirement).map_err(|error| {
+        Error::Parse(format!("Invalid dependency = \"{}\": {}", text, error))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use version;
+    use super::Dependency;
+
+    fn matches(dependency: &str, version: &str) -> bool {
+        let dependency = Dependency::parse(dependency).unwrap();
+        dependency.matches(&version::parse(version).unwrap())
+    }
+
+    #[test]
+    fn empty_dependency_is_parse_error() {
+        assert!(Dependency::parse("").is_err());
+        assert!(Dependency::parse("  ").is_err());
+    }
+
+    #[test]
+    fn name_only_matches_any_version() {
+        let dependency = Dependency::parse("ruby").unwrap();
+        assert_eq!(dependency.name(), "ruby");
+        assert!(dependency.matches(&version::zero()));
+        assert!(matches("ruby", "2.2.3"));
+    }
+
+    #[test]
+    fn name_with_version_matches_this_version_or_greater() {
+        assert_eq!(Dependency::parse("ruby-2.2").unwrap().name(), "ruby");
+        assert!(matches("ruby-2.2", "2.2.0"));
+        assert!(matches("ruby-2.2", "3.0.0"));
+        assert!(!matches("ruby-2.2", "1.9.1"));
+    }
+
+    #[test]
+    fn range_of_predicates() {
+        assert!(matches("ruby >=2.1 <3", "2.2.3"));
+        assert!(!matches("ruby >=2.1 <3", "3.0.0"));
+        assert!(!matches("ruby >=2.1 <3", "2.0.0"));
+    }
+
+    #[test]
+    fn sigil_separated_from_version() {
+        assert!(matches("ruby >= 2.1 < 3", "2.2.3"));
+        assert!(!matches("ruby >= 2.1 < 3", "3.0.0"));
+    }
+
+    #[test]
+    fn tilde_requirement() {
+        assert!(matches("ruby ~2.2.3", "2.2.5"));
+        assert!(!matches("ruby ~2.2.3", "2.3.0"));
+    }
+
+    #[test]
+    fn display_keeps_original_text() {
+        let dependency = Dependency::parse(" ruby >=2.1 <3 ").unwrap();
+        assert_eq!(format!("{}", dependency), "ruby >=2.1 <3");
+    }
+
+    #[test]
+    fn invalid_requirement_is_an_error() {
+        assert!(Dependency::parse("ruby >=two").is_err());
+    }
+}
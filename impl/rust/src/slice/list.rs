@@ -1,36 +1,124 @@
 use std::borrow::Borrow;
-use std::cmp::Ordering;
-use std::fmt::{Debug, Formatter, Result as FormatResult};
 use std::ffi::OsStr;
 use std::fs;
+use std::fs::{DirEntry, ReadDir};
 use std::fs::File;
 use std::io::{ErrorKind, Read};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use semver::Version;
+use error::{Error, Result};
 use os::Os;
-use slice::Slice;
-use slice::section::{Kind, Section};
-use version;
-use VersionMatchStrategy;
-
+use slice::{Bunch, Slice};
+use slice::blank::SliceBlank;
+use slice::dependency::Dependency;
+use slice::resolver;
+use version::{self, SliceVersion};
+use {DuplicateSlicePolicy, PrereleasePolicy, VersionMatchStrategy};
+
+/// All slices from the bunches of a slice root directory
+///
+/// Slices are sorted by bunch version DESC, then by version DESC, then by name DESC.
 #[derive(Debug)]
 pub struct List {
 	slices: Vec<Rc<Slice>>,
+	os_slices: Vec<Rc<Slice>>,
+	diagnostics: Vec<String>,
+	prerelease_policy: PrereleasePolicy,
 }
 
 impl List {
-    pub fn new(root: &Path, os: &Os, os_version_match_strategy: VersionMatchStrategy) -> Result<List, String> {
+    /// Loads slices from the bunches at `root` which support `os`
+    ///
+    /// Files of the `_` directory of these bunches which match the name and the version
+    /// request of `os` are loaded as OS slices.
+    ///
+    /// Bunches whose major version differs from the major version of the tool are skipped
+    /// and reported in `diagnostics`.
+    ///
+    /// With `PrereleasePolicy::Exclude` prerelease slices and slices of prerelease bunches are
+    /// selected only for requests which name a prerelease version.
+    /// # Errors
+    /// `Error::Resolution` if two slices have the same name, version and bunch version, unless
+    /// `duplicate_policy` is `DuplicateSlicePolicy::Warn`
+    pub fn new(root: &Path, os: &Os, duplicate_policy: DuplicateSlicePolicy,
+               prerelease_policy: PrereleasePolicy) -> Result<List> {
         let directories = try!(List::directory_paths_from_slice_root_directory(root));
+        let tool_version = version::tool();
         let mut slices = Vec::new();
+        let mut os_slices = Vec::new();
+        let mut diagnostics = Vec::new();
         for dir in directories {
-            let slices_from_path = List::slices_from_path_for_os(&dir, &os,
-                                                                 &os_version_match_strategy);
-            let mut slices_from_path = try!(slices_from_path);
+            let bunch = try!(Bunch::from_directory_name(&try!(file_name(&dir))));
+            if !bunch.is_compatible_with(&tool_version) {
+                diagnostics.push(format!("Bunch {} is skipped: its major version {} does not \
+                                          match the major version {} of the tool. Path = {}",
+                                         bunch, bunch.version.major, tool_version.major,
+                                         dir.display()));
+                continue;
+            }
+            let os_blanks = try!(List::os_blanks_from_path(&dir, &bunch));
+            let os_blanks = os_blanks.into_iter().filter(|blank| {
+                blank.name == os.name &&
+                version::matches(&blank.version, &os.version, os.version_match_strategy)
+            })
+            .collect::<Vec<_>>();
+            if os_blanks.is_empty() {
+                continue;
+            }
+            let mut slices_from_path = try!(List::slices_from_path(&dir, &bunch));
             slices.append(&mut slices_from_path);
+            os_slices.extend(os_blanks.into_iter().map(List::convert_os_blank_to_slice));
+        }
+        if slices.is_empty() {
+            return Err(Error::Resolution(format!("There are no slices for OS = {}-{} at path = {}",
+                                                 os.name, os.version, root.display())));
         }
-        assert!(!slices.is_empty());
-        Ok(List { slices: List::convert_blanks_to_slices(slices) })
+        let slices = try!(List::remove_duplicates(slices, duplicate_policy, &mut diagnostics));
+        let mut slices = try!(List::convert_blanks_to_slices(slices, prerelease_policy));
+        slices.sort_by(|a, b| b.cmp(a));
+        os_slices.retain(|s| is_allowed(s, &os.version, prerelease_policy));
+        os_slices.sort_by(|a, b| b.cmp(a));
+        Ok(List { slices: slices, os_slices: os_slices, diagnostics: diagnostics,
+                  prerelease_policy: prerelease_policy })
+	}
+
+	/// Returns the base layer for the OS the list is loaded for: the latest matching OS slice
+	pub fn os_slice(&self) -> Option<&Slice> {
+	    self.os_slices.first().map(Borrow::borrow)
+	}
+
+	/// All OS slices matching the OS the list is loaded for, the latest first
+	pub fn os_slices(&self) -> Vec<&Slice> {
+	    self.os_slices.iter().map(Borrow::borrow).collect()
+	}
+
+	/// All slices of the list in its order
+	pub fn slices(&self) -> Vec<&Slice> {
+	    self.slices.iter().map(Borrow::borrow).collect()
+	}
+
+	/// Names of the OSes which the bunches at `root` support, sorted and without duplicates.
+	/// Bunches the tool is not compatible with are skipped.
+	pub fn os_names(root: &Path) -> Result<Vec<String>> {
+	    let tool_version = version::tool();
+	    let mut names = Vec::new();
+	    for dir in try!(List::directory_paths_from_slice_root_directory(root)) {
+	        let bunch = try!(Bunch::from_directory_name(&try!(file_name(&dir))));
+	        if !bunch.is_compatible_with(&tool_version) {
+	            continue;
+	        }
+	        for blank in try!(List::os_blanks_from_path(&dir, &bunch)) {
+	            names.push(blank.name);
+	        }
+	    }
+	    names.sort();
+	    names.dedup();
+	    Ok(names)
+	}
+
+	/// Warnings collected while loading slices
+	pub fn diagnostics(&self) -> &Vec<String> {
+	    &self.diagnostics
 	}
 
 	/// # Panics
@@ -40,19 +128,73 @@ impl List {
 	    self.slices.iter().filter(|s| s.name().contains(name)).map(Borrow::borrow).collect()
 	}
 
-	/// # Panics
-	/// If `name` is empty
-	pub fn find_slice(&self, name: &str, version: &Version,
+	/// Finds the latest slice which matches the request and is allowed by the prerelease
+	/// policy
+	pub fn find_slice(&self, name: &str, version: &SliceVersion,
 	                  version_match_strategy: VersionMatchStrategy) -> Option<&Slice> {
-		let slices = self.slices.iter().filter(|s| s.name() == name);
-		let slices = slices.filter(|s| {
-		    match version_match_strategy {
-			    VersionMatchStrategy::Exact => s.version() == version,
-				VersionMatchStrategy::ExactOrLesser => s.version() <= version,
-				VersionMatchStrategy::ExactOrGreater => s.version() >= version
-			}
-		});
-		slices.max().map(Borrow::borrow)
+		let slices = self.matching_slices(name, version, version_match_strategy).into_iter();
+		slices.filter(|s| is_allowed(s, version, self.prerelease_policy)).max()
+	}
+
+	/// Returns the prerelease slices which match the request but are not selected because of
+	/// the prerelease policy, the latest first
+	pub fn skipped_prereleases(&self, name: &str, version: &SliceVersion,
+	                           version_match_strategy: VersionMatchStrategy) -> Vec<&Slice> {
+		let slices = self.matching_slices(name, version, version_match_strategy).into_iter();
+		let mut slices = slices.filter(|s| !is_allowed(s, version, self.prerelease_policy))
+		                       .collect::<Vec<_>>();
+		slices.sort_by(|a, b| b.cmp(a));
+		slices
+	}
+
+	/// Returns the prerelease slices which match the unresolved dependency `dependency` but are
+	/// not bound to it because of the prerelease policy, the latest first
+	pub fn skipped_dependency_prereleases(&self, dependency: &str) -> Vec<&Slice> {
+		let dependency = match Dependency::parse(dependency) {
+			Ok(dependency) => dependency,
+			Err(_) => return Vec::new(),
+		};
+		let mut slices = self.slices.iter()
+		                            .filter(|s| {
+		                                s.name() == dependency.name() &&
+		                                dependency.matches(s.version()) && s.is_prerelease() &&
+		                                self.prerelease_policy == PrereleasePolicy::Exclude
+		                            })
+		                            .map(Borrow::borrow)
+		                            .collect::<Vec<&Slice>>();
+		slices.sort_by(|a, b| b.cmp(a));
+		slices
+	}
+
+	fn matching_slices(&self, name: &str, version: &SliceVersion,
+	                   version_match_strategy: VersionMatchStrategy) -> Vec<&Slice> {
+		self.slices.iter()
+		           .filter(|s| {
+		               s.name() == name &&
+		               version::matches(s.version(), version, version_match_strategy)
+		           })
+		           .map(Borrow::borrow)
+		           .collect()
+	}
+
+	/// Finds the slice or OS slice with exactly `name` and `version` from the bunch named
+	/// `bunch`, e.g. `slices-1.0.0`
+	pub fn find_slice_in_bunch(&self, name: &str, version: &SliceVersion, bunch: &str)
+	                           -> Option<&Slice> {
+	    self.slices.iter().chain(self.os_slices.iter())
+	                      .find(|s| {
+	                          s.name() == name && s.version() == version &&
+	                          s.bunch().to_string() == bunch
+	                      })
+	                      .map(Borrow::borrow)
+	}
+
+	/// Returns `slices` together with all their nested dependencies, each name only once,
+	/// ordered so that every slice follows its dependencies
+	/// # Errors
+	/// `Error::Resolution` if no version of a dependency satisfies all its constraints
+	pub fn dependency_order<'a>(&'a self, slices: &[&'a Slice]) -> Result<Vec<&'a Slice>> {
+		resolver::resolve(&self.slices, slices, self.prerelease_policy)
 	}
 
 	pub fn unresolved_dependencies(&self) -> Vec<&String> {
@@ -66,168 +208,223 @@ impl List {
 		unresolved_dependencies
 	}
 
-    fn convert_blanks_to_slices(mut blanks: Vec<SliceBlank>) -> Vec<Rc<Slice>> {
-        let mut slices = Vec::new();
-        while let Some(blank) = blanks.pop() {
-            let slice = List::convert_blank_to_slice(blank, &mut blanks, &mut slices);
-            slices.push(slice);
+    /// Checks that no two blanks have the same name, version and bunch version.
+    /// The bunch name and the location of the file inside the bunch are not considered.
+    fn remove_duplicates(blanks: Vec<SliceBlank>, duplicate_policy: DuplicateSlicePolicy,
+                         diagnostics: &mut Vec<String>) -> Result<Vec<SliceBlank>> {
+        let mut unique_blanks: Vec<SliceBlank> = Vec::with_capacity(blanks.len());
+        for blank in blanks {
+            let conflict = unique_blanks.iter().find(|b| **b == blank).map(|b| {
+                format!("Slice {}-{} at path = {} conflicts with slice at path = {}",
+                        blank.name, blank.version, b.path.display(), blank.path.display())
+            });
+            match conflict {
+                None => unique_blanks.push(blank),
+                Some(message) => {
+                    match duplicate_policy {
+                        DuplicateSlicePolicy::Deny => return Err(Error::Resolution(message)),
+                        DuplicateSlicePolicy::Warn => {
+                            diagnostics.push(format!("{}. The latter is ignored", message))
+                        }
+                    }
+                }
+            }
         }
-        slices
+        Ok(unique_blanks)
     }
 
-    fn convert_blank_to_slice(blank: SliceBlank, blanks: &mut Vec<SliceBlank>,
-                              slices: &mut Vec<Rc<Slice>>) -> Rc<Slice> {
-        if blank.dependencies.is_empty() {
-            let slice = Slice::new(blank.name, blank.version, blank.ancestors,
-                                   blank.preparations, Vec::new(), Vec::new());
-            return Rc::new(slice)
+    /// Every `DEP` entry of a blank is bound to the slice which `resolver::select` selects for
+    /// the entry's name when the blank is resolved on its own, so the dependencies shown for a
+    /// slice are the ones which are built with it. Entries without a selected slice are kept as
+    /// unresolved.
+    /// # Errors
+    /// `Error::Resolution` with the full cycle path if the dependencies are cyclic
+    fn convert_blanks_to_slices(blanks: Vec<SliceBlank>, prerelease_policy: PrereleasePolicy)
+                                -> Result<Vec<Rc<Slice>>> {
+        // Blanks are visited by name, so the reported cycle does not depend on the order in
+        // which the files were read
+        let mut roots = (0..blanks.len()).collect::<Vec<_>>();
+        roots.sort_by(|&a, &b| {
+            let (a, b) = (&blanks[a], &blanks[b]);
+            a.name.cmp(&b.name).then(a.cmp(b))
+        });
+        let bindings = try!(List::bind_dependencies(&blanks, &roots, prerelease_policy));
+        let mut order = Vec::with_capacity(blanks.len());
+        let mut path = Vec::new();
+        for root in roots {
+            try!(List::visit_binding(root, &blanks, &bindings, &mut path, &mut order));
         }
-        let mut resolved_dependencies = Vec::new();
-        let mut unresolved_dependencies = Vec::new();
-        for dependency in &blank.dependencies {
-        //
-            let dep_blank = blanks.iter()
-                                  .enumerate()
-                                  .filter(|&(_, b)| b.name == *dependency)
-                                  .map(|(i, b)| (b.version.clone(), i))
-                                  .max();
-            let dep_slice = slices.iter()
-                                  .enumerate()
-                                  .filter(|&(_, s)| s.name() == dependency)
-                                  .map(|(i, s)| (s.version().clone(), i))
-                                  .max();
-            if let Some((dep_slice_version, dep_slice_position)) = dep_slice {
-                if let Some((dep_blank_version, dep_blank_position)) = dep_blank {
-                    if dep_slice_version < dep_blank_version {
-                        let blank = blanks.remove(dep_blank_position);
-                        let dependency = List::convert_blank_to_slice(blank, blanks, slices);
-                        resolved_dependencies.push(dependency.clone());
-                        slices.push(dependency);
-                    } else {
-                        resolved_dependencies.push(slices[dep_slice_position].clone());
+        let mut blanks = blanks.into_iter().map(Some).collect::<Vec<_>>();
+        let mut bindings = bindings.into_iter().map(Some).collect::<Vec<_>>();
+        let mut converted: Vec<Option<Rc<Slice>>> = vec![None; blanks.len()];
+        let mut slices = Vec::with_capacity(blanks.len());
+        for i in order {
+            let blank = blanks[i].take().unwrap();
+            let (resolved_dependencies, unresolved_dependencies) = bindings[i].take().unwrap();
+            let resolved_dependencies = resolved_dependencies.into_iter().map(|d| {
+                converted[d].clone().expect("Dependency is converted before its dependent")
+            })
+            .collect();
+            let slice = Rc::new(Slice::new(blank.name, blank.version, blank.bunch, blank.path,
+                                           blank.sections, blank.dependencies,
+                                           resolved_dependencies, unresolved_dependencies));
+            converted[i] = Some(slice.clone());
+            slices.push(slice);
+        }
+        Ok(slices)
+    }
+
+    /// Returns for every blank the indices of the blanks its dependencies are bound to and the
+    /// dependencies which are unresolved
+    fn bind_dependencies(blanks: &[SliceBlank], roots: &[usize],
+                         prerelease_policy: PrereleasePolicy)
+                         -> Result<Vec<(Vec<usize>, Vec<String>)>> {
+        let slices = blanks.iter().map(|blank| {
+            Rc::new(Slice::new(blank.name.clone(), blank.version.clone(), blank.bunch.clone(),
+                               blank.path.clone(), Vec::new(), blank.dependencies.clone(),
+                               Vec::new(), Vec::new()))
+        })
+        .collect::<Vec<_>>();
+        let mut bindings = vec![(Vec::new(), Vec::new()); slices.len()];
+        for &root in roots {
+            let slice = &slices[root];
+            let selection = try!(resolver::select(&slices, &[slice], prerelease_policy));
+            let mut resolved = Vec::new();
+            let mut unresolved = Vec::new();
+            for dependency in slice.dependencies() {
+                let is_unresolved = selection.unresolved.iter().any(|u| {
+                    u.dependent == &**slice && u.dependency == dependency
+                });
+                let selected = selection.order.iter().find(|s| s.name() == dependency.name());
+                let index = selected.and_then(|selected| {
+                    slices.iter().position(|s| &**s == *selected)
+                });
+                match index {
+                    Some(index) if !is_unresolved => {
+                        if !resolved.contains(&index) {
+                            resolved.push(index);
+                        }
                     }
-                } else {
-                    resolved_dependencies.push(slices[dep_slice_position].clone());
-                }
-            } else {
-                if let Some((_, dep_blank_position)) = dep_blank {
-                    let blank = blanks.remove(dep_blank_position);
-                    let dependency = List::convert_blank_to_slice(blank, blanks, slices);
-                    resolved_dependencies.push(dependency.clone());
-                    slices.push(dependency);
-                } else {
-                    unresolved_dependencies.push(dependency.clone());
+                    _ => unresolved.push(dependency.to_string()),
                 }
             }
+            bindings[root] = (resolved, unresolved);
         }
-        let slice = Slice::new(blank.name, blank.version, blank.ancestors, blank.preparations,
-                               resolved_dependencies, unresolved_dependencies);
-        Rc::new(slice)
+        Ok(bindings)
     }
 
-    fn slices_from_path_for_os(path: &Path, os: &Os,
-                               os_version_match_strategy: &VersionMatchStrategy)
-                               -> Result<Vec<SliceBlank>, String> {
-	    let oses = try!(List::get_oses(&path));
-        let directory_contains_required_os = oses.iter().any(|os_from_list| {
-            if os_from_list.name != os.name {
-                return false;
-            }
-            match *os_version_match_strategy {
-                VersionMatchStrategy::Exact => os_from_list.version == os.version,
-                VersionMatchStrategy::ExactOrLesser => os_from_list.version <= os.version,
-                VersionMatchStrategy::ExactOrGreater => os_from_list.version >= os.version
-            }
-        });
-		if !directory_contains_required_os {
-			return Ok(Vec::new());
-		}
-		Ok(List::slices_from_path(path))
-	}
+    fn visit_binding(index: usize, blanks: &[SliceBlank], bindings: &[(Vec<usize>, Vec<String>)],
+                     path: &mut Vec<usize>, order: &mut Vec<usize>) -> Result<()> {
+        if order.contains(&index) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|&i| i == index) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(index);
+            let names = cycle.iter()
+                             .map(|&i| format!("{}-{}", blanks[i].name, blanks[i].version))
+                             .collect::<Vec<_>>();
+            return Err(Error::Resolution(format!("Cyclic dependency: {}",
+                                                 names.join(" -> "))));
+        }
+        path.push(index);
+        for &dependency in &bindings[index].0 {
+            try!(List::visit_binding(dependency, blanks, bindings, path, order));
+        }
+        path.pop();
+        order.push(index);
+        Ok(())
+    }
 
-	fn slices_from_path(path: &Path) -> Vec<SliceBlank> {
+    /// OS slices have no dependencies, they are the base layer for all other slices
+    fn convert_os_blank_to_slice(blank: SliceBlank) -> Rc<Slice> {
+        Rc::new(Slice::new(blank.name, blank.version, blank.bunch, blank.path, blank.sections,
+                           Vec::new(), Vec::new(), Vec::new()))
+    }
+
+	fn slices_from_path(path: &Path, bunch: &Bunch) -> Result<Vec<SliceBlank>> {
 		let mut slices = Vec::new();
-		let entries = fs::read_dir(path).unwrap();
-		for entry in entries {
-			let entry = entry.unwrap();
-			let metadata = entry.metadata().unwrap();
-			if metadata.is_dir() {
-				let mut nested_dir_slices = List::slices_from_path(&entry.path());
+		for entry in try!(read_dir_entries(path)) {
+			if try!(is_dir(&entry)) {
+				let mut nested_dir_slices = try!(List::slices_from_path(&entry.path(), bunch));
 				slices.append(&mut nested_dir_slices);
 			} else {
 			    let path = entry.path();
-				if let Some(extension) = path.extension() {
-				    match extension.to_str().unwrap() {
+				if let Some(extension) = path.extension().and_then(OsStr::to_str) {
+				    match extension {
 					    "txt" => continue,
 						"md" => continue,
 						_ => {}
 					}
 				}
-				slices.push(List::slice_from_path(&path))
+				slices.push(try!(List::slice_from_path(&path, bunch)))
 			}
 		}
-		slices
+		Ok(slices)
 	}
 
-	fn slice_from_path(path: &Path) -> SliceBlank {
-	    let file_name = path.file_name().map(OsStr::to_str).unwrap().unwrap();
-		let (name, version) = version::extract_name_and_version(&file_name);
+	fn slice_from_path(path: &Path, bunch: &Bunch) -> Result<SliceBlank> {
+	    let file_name = try!(file_name(path));
+		let (name, version) = try!(version::extract_name_and_version(&file_name));
 		match File::open(path) {
-		    Ok(mut file) => List::slice_from_file(name, version, path, &mut file),
-			Err(error) => panic!("File at path = {} was not open because of error: {}",
-				       			 path.display(), error)
+		    Ok(mut file) => List::slice_from_file(name, version, bunch, path, &mut file),
+			Err(error) => Err(Error::io(path, error))
 		}
 	}
 
-    fn slice_from_file(name: String, version: Version, path: &Path, file: &mut File)
-                       -> SliceBlank {
+    fn slice_from_file(name: String, version: SliceVersion, bunch: &Bunch, path: &Path,
+                       file: &mut File) -> Result<SliceBlank> {
 		let mut file_content = String::new();
 		match file.read_to_string(&mut file_content) {
 			Ok(_) => {
 			    let lines = file_content.split('\n')
 				                        .map(|n: &str| n.trim().to_string())
 										.collect();
-                SliceBlank::from_lines(name, version, lines)
+                SliceBlank::from_lines(name, version, bunch.clone(), path.to_path_buf(), lines)
 			}
-			Err(error) => panic!("File at path = {} cannot be read because of error: {}",
-			                     path.display(), error)
+			Err(error) => Err(Error::io(path, error))
 		}
 	}
 
-    fn directory_paths_from_slice_root_directory(path: &Path) -> Result<Vec<PathBuf>, String> {
+    fn directory_paths_from_slice_root_directory(path: &Path) -> Result<Vec<PathBuf>> {
         match fs::read_dir(path) {
-		    Ok(entries) => Ok(entries.map(Result::unwrap)
-                                     .filter(|e| e.metadata().unwrap().is_dir())
-                                     .map(|e| e.path())
-                                     .collect()),
+		    Ok(entries) => {
+		        let mut paths = Vec::new();
+		        for entry in try!(sorted_entries(path, entries)) {
+		            if try!(is_dir(&entry)) {
+		                paths.push(entry.path());
+		            }
+		        }
+		        Ok(paths)
+		    }
 			Err(ref error) if error.kind() == ErrorKind::NotFound => {
 			    let error = format!("Slice root directory is not exists. Path = {}",
 				                    path.display());
-				Err(error)
+				Err(Error::Resolution(error))
 			}
-			Err(error) => Err(format!("Unknown error = {}", error))
+			Err(error) => Err(Error::io(path, error))
 		}
     }
 
-	fn get_oses(path: &Path) -> Result<Vec<Os>, String> {
+	/// Loads OS slices from the `_` directory of the bunch at `path`
+	fn os_blanks_from_path(path: &Path, bunch: &Bunch) -> Result<Vec<SliceBlank>> {
 	    let mut path = path.to_path_buf();
 		path.push("_");
 		match fs::read_dir(&path) {
 		    Ok(entries) => {
-			    let os_list = entries.map(|e| e.unwrap())
-				                     .filter(|e| e.metadata().unwrap().is_file())
-									 .map(|f| {
-					let file_name = f.path().file_name().unwrap().to_str().unwrap().to_string();
-					let (name, version) = version::extract_name_and_version(&file_name);
-					Os { name: name, version: version }
-									 })
-									 .collect::<Vec<_>>();
+			    let mut os_list = Vec::new();
+			    for entry in try!(sorted_entries(&path, entries)) {
+			        if try!(is_dir(&entry)) {
+			            continue;
+			        }
+					os_list.push(try!(List::slice_from_path(&entry.path(), bunch)));
+				}
 				Ok(os_list)
 			}
 			Err(ref error) if error.kind() == ErrorKind::NotFound => {
-				Err(get_error_for_nonexistent_os_dir(path))
+				Err(Error::Resolution(get_error_for_nonexistent_os_dir(path)))
 			}
-			Err(error) => Err(format!("{}", error))
+			Err(error) => Err(Error::io(path, error))
 		}
 	}
 }
@@ -236,73 +433,52 @@ fn get_error_for_nonexistent_os_dir<P:AsRef<Path>>(path: P) -> String {
 	format!("There is no \"_\" directory at {}", path.as_ref().display())
 }
 
-#[derive(Hash)]
-struct SliceBlank {
-    name: String,
-	version: Version,
-	ancestors: Vec<String>,
-	dependencies: Vec<String>,
-	preparations: Vec<String>
-}
-
-impl SliceBlank {
-    fn from_lines<L:Borrow<str>+Debug>(name: String, version: Version, mut lines: Vec<L>)
-	                             -> SliceBlank {
-		let mut slice_blank = SliceBlank { name: name, version: version, ancestors: Vec::new(),
-                                           dependencies: Vec::new(), preparations: Vec::new() };
-        while !lines.is_empty() {
-            let (section, remaining_lines) = Section::from_lines(lines);
-            lines = remaining_lines;
-            if let Some(section) = section {
-                match section.kind {
-                    Kind::Dep => slice_blank.dependencies = section.items,
-                    Kind::From => slice_blank.ancestors = section.items,
-                    Kind::Run => slice_blank.preparations = section.items,
-                    _ => {}
-                }
-            } else {
-                break;
-            }
-        }
-        slice_blank
-	}
-}
-
-impl PartialEq for SliceBlank {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.version == other.version
+fn read_dir_entries(path: &Path) -> Result<Vec<DirEntry>> {
+    match fs::read_dir(path) {
+        Ok(entries) => sorted_entries(path, entries),
+        Err(error) => Err(Error::io(path, error))
     }
 }
 
-impl PartialOrd for SliceBlank {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.version.partial_cmp(&other.version)
+/// Collects `entries` of the directory at `path` sorted by their paths
+fn sorted_entries(path: &Path, entries: ReadDir) -> Result<Vec<DirEntry>> {
+    let mut result = Vec::new();
+    for entry in entries {
+        result.push(try!(entry.map_err(|e| Error::io(path, e))));
     }
+    result.sort_by(|a, b| a.path().cmp(&b.path()));
+    Ok(result)
 }
 
-impl Eq for SliceBlank {
+fn is_dir(entry: &DirEntry) -> Result<bool> {
+    entry.metadata().map(|m| m.is_dir()).map_err(|e| Error::io(entry.path(), e))
 }
 
-impl Ord for SliceBlank {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.version.cmp(&other.version)
+fn file_name(path: &Path) -> Result<String> {
+    match path.file_name().and_then(OsStr::to_str) {
+        Some(file_name) => Ok(file_name.to_string()),
+        None => Err(Error::Parse(format!("File name is not valid UTF-8. Path = {}",
+                                         path.display())))
     }
 }
 
-impl Debug for SliceBlank {
-    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
-        write!(formatter, "SliceBlank({})", self.name)
-    }
+/// Checks whether `slice` can be selected for a request of `requested_version`
+fn is_allowed(slice: &Slice, requested_version: &SliceVersion, policy: PrereleasePolicy)
+              -> bool {
+    !slice.is_prerelease() || requested_version.is_prerelease() ||
+    policy == PrereleasePolicy::Include
 }
 
 #[cfg(test)]
 mod tests {
 	use std::env;
-	use std::path::Path;
+	use std::path::{Path, PathBuf};
 	use std::rc::Rc;
 	use os::Os;
-	use slice::{List, Slice};
-	use VersionMatchStrategy;
+	use slice::{Bunch, Kind, List, Section, Slice};
+	use slice::blank::SliceBlank;
+	use slice::dependency::Dependency;
+	use {DuplicateSlicePolicy, PrereleasePolicy, VersionMatchStrategy};
 	use version;
 
     #[test]
@@ -340,41 +516,93 @@ mod tests {
         let mut path = env::current_dir().expect("Current directory is not set").to_path_buf();
 		path.push("test_slices");
         path.push("slices-du-0.0.2");
-        let oses = List::get_oses(&path).expect("Oses were not retrieved");
-        assert_eq!(oses, vec![Os { name: String::from("debian"), version: version::parse("8.2") },
-                              Os { name: String::from("ubuntu"),
-                                   version: version::parse("14.3.2") }]);
+        let oses = List::os_blanks_from_path(&path, &create_bunch())
+                        .expect("Oses were not retrieved");
+        let oses = oses.into_iter().map(|b| (b.name, b.version)).collect::<Vec<_>>();
+        assert_eq!(oses, vec![(String::from("debian"), version::parse("8.2").unwrap()),
+                              (String::from("ubuntu"), version::parse("14.3.2").unwrap())]);
+    }
+
+    #[test]
+    fn os_names_of_test_slices() {
+        let mut path = env::current_dir().expect("Current directory is not set").to_path_buf();
+		path.push("test_slices");
+        assert_eq!(List::os_names(&path).unwrap(), vec!["debian", "ubuntu"]);
+    }
+
+    #[test]
+    fn os_slice_is_loaded_with_its_sections() {
+        let mut path = env::current_dir().expect("Current directory is not set").to_path_buf();
+		path.push("test_slices");
+	    let os = Os::parse("debian").unwrap();
+	    let list = List::new(&path, &os, DuplicateSlicePolicy::Deny, PrereleasePolicy::Exclude)
+	                   .unwrap();
+	    let os_slice = list.os_slice().expect("OS slice is not loaded");
+	    assert_eq!(os_slice.name(), "debian");
+	    assert_eq!(os_slice.version(), &version::parse("8.2").unwrap());
+	    assert_eq!(os_slice.sections(),
+	               &vec![Section::new(Kind::From, vec![String::from("debian:jessie")])]);
     }
 
     #[test]
 	fn list_from_test_slices() {
 	    let mut path = env::current_dir().expect("Current directory is not set").to_path_buf();
 		path.push("test_slices");
-	    let os = Os { name: String::from("debian"), version: version::zero() };
-	    let list = List::new(&path, &os, VersionMatchStrategy::ExactOrGreater).unwrap();
-        match list.find_slice("jekyll", &version::zero(), VersionMatchStrategy::ExactOrGreater) {
+	    let os = Os::parse("debian").unwrap();
+	    let list = List::new(&path, &os, DuplicateSlicePolicy::Deny, PrereleasePolicy::Exclude)
+	                   .unwrap();
+        let beta = version::parse("3.0.0-beta").unwrap();
+        match list.find_slice("jekyll", &beta, VersionMatchStrategy::ExactOrGreater) {
             Some(_) => {}
             None => panic!("Requested slice was not found")
         }
 	}
 
+    #[test]
+	fn prerelease_of_test_slices_is_skipped_unless_requested() {
+	    let mut path = env::current_dir().expect("Current directory is not set").to_path_buf();
+		path.push("test_slices");
+	    let os = Os::parse("debian").unwrap();
+	    let list = List::new(&path, &os, DuplicateSlicePolicy::Deny, PrereleasePolicy::Exclude)
+	                   .unwrap();
+	    let strategy = VersionMatchStrategy::ExactOrGreater;
+	    assert!(list.find_slice("jekyll", &version::zero(), strategy).is_none());
+	    let skipped = list.skipped_prereleases("jekyll", &version::zero(), strategy);
+	    assert_eq!(skipped.iter().map(|s| s.version().to_string()).collect::<Vec<_>>(),
+	               vec!["3.0.0-beta.9"]);
+	    let list = List::new(&path, &os, DuplicateSlicePolicy::Deny, PrereleasePolicy::Include)
+	                   .unwrap();
+	    assert!(list.find_slice("jekyll", &version::zero(), strategy).is_some());
+	    assert!(list.skipped_prereleases("jekyll", &version::zero(), strategy).is_empty());
+	}
+
 	#[should_panic]
 	#[test]
 	fn list_from_nonexistent_directory() {
 	    let path = Path::new("/non/existent/directory");
-		let os = Os { name: String::from("debian"), version: version::zero() };
-	    let _ = List::new(&path, &os, VersionMatchStrategy::ExactOrGreater).unwrap();
+		let os = Os::parse("debian").unwrap();
+	    let _ = List::new(&path, &os, DuplicateSlicePolicy::Deny, PrereleasePolicy::Exclude)
+	                .unwrap();
+	}
+
+	fn create_bunch() -> Bunch {
+	    Bunch::new(String::from("slices"), version::zero())
+	}
+
+	fn create_sections() -> Vec<Section> {
+	    vec![Section::new(Kind::Run, vec![String::from("nothing")])]
 	}
 
 	#[test]
 	fn find_similar_slices() {
 	    let create_slice = |name| {
-		    Slice::new(String::from(name), version::zero(), Vec::new(),
-			           vec![String::from("nothing")], Vec::new(), Vec::new())
+		    Slice::new(String::from(name), version::zero(), create_bunch(), PathBuf::from(name),
+			           create_sections(), Vec::new(), Vec::new(), Vec::new())
 		};
 	    let slices = vec![create_slice("a"), create_slice("ab"), create_slice("bc")];
 		let slices = slices.into_iter().map(|s| Rc::new(s)).collect();
-	    let list = List { slices: slices };
+	    let list = List { slices: slices, os_slices: Vec::new(), diagnostics: Vec::new(),
+	                      prerelease_policy: PrereleasePolicy::Exclude };
 		let similar_slices = list.find_similar_slices("a");
 		let similar_slice_names = similar_slices.into_iter().map(Slice::name).collect::<Vec<_>>();
 		assert_eq!(similar_slice_names, vec!["a", "ab"]);
@@ -383,44 +611,165 @@ mod tests {
 	fn prepare_list_to_test_find_slice() -> List {
 		let create_slice_with_version = |name, version| {
 		    let name = String::from(name);
-			let preparations = vec![String::from("nothing")];
-			Slice::new(name, version, Vec::new(), preparations, Vec::new(), Vec::new())
+			Slice::new(name, version, create_bunch(), PathBuf::new(), create_sections(),
+			           Vec::new(), Vec::new(), Vec::new())
 		};
 		let create_slice = |name| {
 		    create_slice_with_version(name, version::zero())
 		};
 	    let slices = vec![create_slice("a"),
-		                  create_slice_with_version("a", version::parse("0.5.0")),
-		                  create_slice_with_version("a", version::parse("1.0.0"))];
+		                  create_slice_with_version("a", version::parse("0.5.0").unwrap()),
+		                  create_slice_with_version("a", version::parse("1.0.0").unwrap())];
 		let slices = slices.into_iter().map(|s| Rc::new(s)).collect();
-	    List { slices: slices }
+	    List { slices: slices, os_slices: Vec::new(), diagnostics: Vec::new(),
+	           prerelease_policy: PrereleasePolicy::Exclude }
 	}
 
 	#[test]
 	fn find_slice_with_exact_version() {
 		let list = prepare_list_to_test_find_slice();
-		let slice = list.find_slice("a", &version::parse("0.5.0"), VersionMatchStrategy::Exact);
+		let slice = list.find_slice("a", &version::parse("0.5.0").unwrap(), VersionMatchStrategy::Exact);
 		let slice = slice.expect("slice was not found");
 		assert_eq!(slice.name(), "a");
-		assert_eq!(*slice.version(), version::parse("0.5.0"));
+		assert_eq!(*slice.version(), version::parse("0.5.0").unwrap());
 	}
 
 	#[test]
 	fn find_slice_with_exact_or_lesser_version() {
 		let list = prepare_list_to_test_find_slice();
-		let slice = list.find_slice("a", &version::parse("0.7.0"), VersionMatchStrategy::ExactOrLesser);
+		let slice = list.find_slice("a", &version::parse("0.7.0").unwrap(), VersionMatchStrategy::ExactOrLesser);
 		let slice = slice.expect("slice was not found");
 		assert_eq!(slice.name(), "a");
-		assert_eq!(*slice.version(), version::parse("0.5.0"));
+		assert_eq!(*slice.version(), version::parse("0.5.0").unwrap());
 	}
 
 	#[test]
 	fn find_slice_with_exact_or_greater_version() {
 		let list = prepare_list_to_test_find_slice();
-		let slice = list.find_slice("a", &version::parse("0.7.0"), VersionMatchStrategy::ExactOrGreater);
+		let slice = list.find_slice("a", &version::parse("0.7.0").unwrap(), VersionMatchStrategy::ExactOrGreater);
 		let slice = slice.expect("slice was not found");
 		assert_eq!(slice.name(), "a");
-		assert_eq!(*slice.version(), version::parse("1.0.0"));
+		assert_eq!(*slice.version(), version::parse("1.0.0").unwrap());
+	}
+
+	#[test]
+	fn find_slice_with_compatible_version() {
+		let list = prepare_list_to_test_find_slice();
+		let slice = list.find_slice("a", &version::parse("0.5.0").unwrap(),
+		                            VersionMatchStrategy::Compatible);
+		assert_eq!(*slice.expect("slice was not found").version(), version::parse("0.5.0").unwrap());
+		let slice = list.find_slice("a", &version::zero(), VersionMatchStrategy::Latest);
+		assert_eq!(*slice.expect("slice was not found").version(), version::parse("1.0.0").unwrap());
+	}
+
+	#[test]
+	fn find_slice_prefers_slice_from_bunch_with_greater_version() {
+		let create_slice = |version, bunch_version| {
+		    let bunch = Bunch::new(String::from("slices"), version::parse(bunch_version).unwrap());
+			Rc::new(Slice::new(String::from("a"), version::parse(version).unwrap(), bunch,
+			                   PathBuf::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()))
+		};
+	    let mut list = List { slices: vec![create_slice("2.0.0", "1.2.3"),
+	                                       create_slice("1.0.0", "1.2.4-rc.1")],
+	                          os_slices: Vec::new(), diagnostics: Vec::new(),
+	                          prerelease_policy: PrereleasePolicy::Include };
+		let slice = list.find_slice("a", &version::zero(), VersionMatchStrategy::ExactOrGreater);
+		let slice = slice.expect("slice was not found");
+		assert_eq!(*slice.version(), version::parse("1.0.0").unwrap());
+		list.prerelease_policy = PrereleasePolicy::Exclude;
+		let slice = list.find_slice("a", &version::zero(), VersionMatchStrategy::ExactOrGreater);
+		assert_eq!(*slice.expect("slice was not found").version(), version::parse("2.0.0").unwrap());
+		let skipped = list.skipped_prereleases("a", &version::zero(),
+		                                       VersionMatchStrategy::ExactOrGreater);
+		assert_eq!(skipped.iter().map(|s| s.bunch().to_string()).collect::<Vec<_>>(),
+		           vec!["slices-1.2.4-rc.1"]);
+	}
+
+	#[test]
+	fn list_from_test_slices_is_sorted() {
+	    let mut path = env::current_dir().expect("Current directory is not set").to_path_buf();
+		path.push("test_slices");
+	    let os = Os::parse("debian").unwrap();
+	    let list = List::new(&path, &os, DuplicateSlicePolicy::Deny, PrereleasePolicy::Exclude)
+	                   .unwrap();
+		assert!(list.slices.windows(2).all(|w| w[0] >= w[1]));
+		assert!(list.diagnostics().is_empty());
+	}
+
+	fn create_blank(path: &str, version: &str, bunch_name: &str) -> SliceBlank {
+	    let bunch = Bunch::new(String::from(bunch_name), version::parse("1.2.2").unwrap());
+	    SliceBlank { name: String::from("mysql"), version: version::parse(version).unwrap(),
+	                 bunch: bunch, path: PathBuf::from(path), dependencies: Vec::new(),
+	                 sections: Vec::new() }
+	}
+
+	#[test]
+	fn duplicates_across_bunches_are_denied() {
+	    let blanks = vec![create_blank("myslices-1.2.2/m/mysql-4.1", "4.1", "myslices"),
+	                      create_blank("slices-1.2.2/m/m/mysql-4.1.0", "4.1.0", "slices")];
+	    let mut diagnostics = Vec::new();
+	    match List::remove_duplicates(blanks, DuplicateSlicePolicy::Deny, &mut diagnostics) {
+	        Ok(_) => panic!("Duplicate was not detected"),
+	        Err(error) => {
+	            let message = format!("{}", error);
+	            assert!(message.contains("myslices-1.2.2/m/mysql-4.1 "), message);
+	            assert!(message.contains("slices-1.2.2/m/m/mysql-4.1.0"), message);
+	        }
+	    }
+	}
+
+	#[test]
+	fn duplicates_are_ignored_with_warning() {
+	    let blanks = vec![create_blank("slices-1.2.2/a/mysql", "4.1", "slices"),
+	                      create_blank("slices-1.2.2/a/a/a/mysql", "4.1", "slices"),
+	                      create_blank("slices-1.2.2/a/mysql-5", "5", "slices")];
+	    let mut diagnostics = Vec::new();
+	    let blanks = List::remove_duplicates(blanks, DuplicateSlicePolicy::Warn, &mut diagnostics)
+	                     .unwrap();
+	    let paths = blanks.iter().map(|b| b.path.to_str().unwrap()).collect::<Vec<_>>();
+	    assert_eq!(paths, vec!["slices-1.2.2/a/mysql", "slices-1.2.2/a/mysql-5"]);
+	    assert_eq!(diagnostics.len(), 1);
+	}
+
+	fn create_dependent_blank(name: &str, version: &str, dependencies: Vec<&str>) -> SliceBlank {
+	    let bunch = Bunch::new(String::from("slices"), version::parse("1.2.2").unwrap());
+	    let dependencies = dependencies.into_iter().map(|d| Dependency::parse(d).unwrap())
+	                                   .collect();
+	    SliceBlank { name: String::from(name), version: version::parse(version).unwrap(),
+	                 bunch: bunch, path: PathBuf::from(name), dependencies: dependencies,
+	                 sections: Vec::new() }
+	}
+
+	#[test]
+	fn dependencies_are_bound_to_the_versions_the_resolver_selects() {
+	    let blanks = vec![create_dependent_blank("ruby", "3.0.0", vec![]),
+	                      create_dependent_blank("ruby", "2.2.3", vec![]),
+	                      create_dependent_blank("rails", "4", vec!["ruby <3"]),
+	                      create_dependent_blank("app", "1", vec!["ruby", "rails", "git"])];
+	    let slices = List::convert_blanks_to_slices(blanks, PrereleasePolicy::Exclude).unwrap();
+	    let app = slices.iter().find(|s| s.name() == "app").unwrap();
+	    let resolved = app.resolved_dependencies().iter()
+	                      .map(|s| format!("{}-{}", s.name(), s.version()))
+	                      .collect::<Vec<_>>();
+	    assert_eq!(resolved, vec!["ruby-2.2.3", "rails-4.0.0"]);
+	    assert_eq!(*app.unresolved_dependencies(), vec![String::from("git")]);
+	    let list = List { slices: slices.clone(), os_slices: Vec::new(), diagnostics: Vec::new(),
+	                      prerelease_policy: PrereleasePolicy::Exclude };
+	    let order = list.dependency_order(&[app]).unwrap_err();
+	    assert!(format!("{}", order).contains("git"));
+	}
+
+	#[test]
+	fn cyclic_dependencies_are_denied() {
+	    let blanks = vec![create_dependent_blank("b", "1", vec!["a"]),
+	                      create_dependent_blank("a", "1", vec!["b"])];
+	    match List::convert_blanks_to_slices(blanks, PrereleasePolicy::Exclude) {
+	        Ok(_) => panic!("Cycle was not detected"),
+	        Err(error) => {
+	            let message = format!("{}", error);
+	            assert!(message.contains("a-1.0.0 -> b-1.0.0 -> a-1.0.0"), message);
+	        }
+	    }
 	}
 
 	#[test]
@@ -428,15 +777,17 @@ mod tests {
 		let create_slice = |name, resolved_dependencies, unresolved_dependencies: Vec<&str>| {
 		    let unresolved_dependencies = unresolved_dependencies.into_iter().map(str::to_string)
 			                                                                 .collect::<Vec<_>>();
-		    let slice = Slice::new(String::from(name), version::zero(), Vec::new(),
-			                       vec![String::from("nothing")], resolved_dependencies,
+		    let slice = Slice::new(String::from(name), version::zero(), create_bunch(),
+			                       PathBuf::from(name), create_sections(), Vec::new(), resolved_dependencies,
 								   unresolved_dependencies);
 			Rc::new(slice)
 		};
 		let first_slice = create_slice("first_slice", Vec::new(), vec!["missing_dep_from_first_slice"]);
 		let second_slice = create_slice("second_slice", vec![first_slice.clone()], Vec::new());
 		let third_slice = create_slice("third_slice", Vec::new(), vec!["missing_dep_from_third_slice"]);
-	    let list = List { slices: vec![first_slice, second_slice, third_slice] };
+	    let list = List { slices: vec![first_slice, second_slice, third_slice],
+	                      os_slices: Vec::new(), diagnostics: Vec::new(),
+	                      prerelease_policy: PrereleasePolicy::Exclude };
 		let unresolved_deps = list.unresolved_dependencies();
 		assert_eq!(unresolved_deps, vec![&String::from("missing_dep_from_first_slice"),
 										 &String::from("missing_dep_from_third_slice")]);
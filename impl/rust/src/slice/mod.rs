@@ -1,6 +1,12 @@
+pub use self::bunch::Bunch;
 pub use self::slice::Slice;
 pub use self::list::List;
+pub use self::section::{Kind, Section};
 
+mod blank;
+mod bunch;
+mod dependency;
 mod list;
+mod resolver;
 mod section;
 mod slice;
\ No newline at end of file
@@ -0,0 +1,355 @@
+use std::rc::Rc;
+use PrereleasePolicy;
+use error::{Error, Result};
+use slice::Slice;
+use slice::dependency::Dependency;
+
+/// Dependency which no slice is selected for
+pub struct Unresolved<'a> {
+    /// Slice which declares the dependency
+    pub dependent: &'a Slice,
+    pub dependency: &'a Dependency,
+    /// Why no slice is selected, e.g. the constraints no version satisfies
+    pub reason: String,
+}
+
+/// Slices selected by `select`
+pub struct Selection<'a> {
+    /// Selected slices ordered so that every slice follows its dependencies
+    pub order: Vec<&'a Slice>,
+    pub unresolved: Vec<Unresolved<'a>>,
+}
+
+/// Resolves `requested` slices together with all their nested dependencies against `slices`
+/// and fails on the first dependency which cannot be resolved
+/// # Errors
+/// `Error::Resolution` if a dependency is unresolved as described by `select`, or `select`
+/// fails
+pub fn resolve<'a>(slices: &'a [Rc<Slice>], requested: &[&'a Slice],
+                   prerelease_policy: PrereleasePolicy)
+                   -> Result<Vec<&'a Slice>> {
+    let selection = try!(select(slices, requested, prerelease_policy));
+    match selection.unresolved.into_iter().next() {
+        Some(unresolved) => Err(Error::Resolution(unresolved.reason)),
+        None => Ok(selection.order),
+    }
+}
+
+/// Selects `requested` slices together with all their nested dependencies from `slices`
+///
+/// Every slice name is resolved to a single version: the highest one which satisfies all
+/// constraints placed on the name by the slices being resolved. With
+/// `PrereleasePolicy::Exclude` only releases are selected for dependencies. Requested slices
+/// are taken as they are. Dependencies which are missing from `slices`, or which no version
+/// satisfies, or only skipped prereleases do, are unresolved.
+/// # Errors
+/// `Error::Resolution` if dependencies are cyclic or the selection does not settle
+pub fn select<'a>(slices: &'a [Rc<Slice>], requested: &[&'a Slice],
+                  prerelease_policy: PrereleasePolicy)
+                  -> Result<Selection<'a>> {
+    let mut constraints: Vec<Constraint<'a>> = Vec::new();
+    let mut previous_constraints: Vec<Vec<Constraint<'a>>> = Vec::new();
+    loop {
+        let (order, met, unresolved) = {
+            let mut resolution = Resolution { slices: slices, requested: requested,
+                                              prerelease_policy: prerelease_policy,
+                                              constraints: &constraints, order: Vec::new(),
+                                              path: Vec::new(), met: Vec::new(),
+                                              unresolved: Vec::new() };
+            for slice in requested {
+                try!(resolution.visit(slice));
+            }
+            (resolution.order, resolution.met, resolution.unresolved)
+        };
+        if is_same_set(&met, &constraints) {
+            return Ok(Selection { order: order, unresolved: unresolved });
+        }
+        if previous_constraints.iter().any(|c| is_same_set(c, &met)) {
+            let names = requested.iter()
+                                 .map(|s| format!("{}-{}", s.name(), s.version()))
+                                 .collect::<Vec<_>>();
+            return Err(Error::Resolution(format!("Dependencies of {} do not settle on a single \
+                                                  version of every slice",
+                                                 names.join(", "))));
+        }
+        previous_constraints.push(constraints);
+        constraints = met;
+    }
+}
+
+fn is_same_set(a: &[Constraint], b: &[Constraint]) -> bool {
+    a.iter().all(|c| b.contains(c)) && b.iter().all(|c| a.contains(c))
+}
+
+/// Dependency declared by a slice
+#[derive(PartialEq)]
+struct Constraint<'a> {
+    dependency: &'a Dependency,
+    dependent: &'a Slice,
+}
+
+/// A single pass over the dependency closure
+///
+/// Versions are selected with the constraints met during the previous pass and the ones met
+/// so far. Constraints of slices which the pass does not select are dropped, so `resolve`
+/// repeats passes until the constraints met are the ones the pass started with.
+struct Resolution<'a: 'b, 'b> {
+    slices: &'a [Rc<Slice>],
+    requested: &'b [&'a Slice],
+    prerelease_policy: PrereleasePolicy,
+    constraints: &'b [Constraint<'a>],
+    order: Vec<&'a Slice>,
+    path: Vec<&'a Slice>,
+    met: Vec<Constraint<'a>>,
+    /// Reported only if the pass is the last one, a later pass may drop the constraints which
+    /// make a dependency unresolved
+    unresolved: Vec<Unresolved<'a>>,
+}
+
+impl<'a, 'b> Resolution<'a, 'b> {
+    fn visit(&mut self, slice: &'a Slice) -> Result<()> {
+        if self.order.contains(&slice) {
+            return Ok(());
+        }
+        if let Some(start) = self.path.iter().position(|s| *s == slice) {
+            let mut cycle = self.path[start..].iter()
+                                              .map(|s| format!("{}-{}", s.name(), s.version()))
+                                              .collect::<Vec<_>>();
+            cycle.push(format!("{}-{}", slice.name(), slice.version()));
+            return Err(Error::Resolution(format!("Cyclic dependency: {}", cycle.join(" -> "))));
+        }
+        self.path.push(slice);
+        for dependency in slice.dependencies() {
+            let constraint = Constraint { dependency: dependency, dependent: slice };
+            if !self.met.contains(&constraint) {
+                self.met.push(constraint);
+            }
+            match self.select(dependency) {
+                Ok(selected) => try!(self.visit(selected)),
+                Err(reason) => {
+                    self.unresolved.push(Unresolved { dependent: slice, dependency: dependency,
+                                                      reason: reason })
+                }
+            }
+        }
+        self.path.pop();
+        self.order.push(slice);
+        Ok(())
+    }
+
+    /// # Errors
+    /// Why no version is selected
+    fn select(&self, dependency: &'a Dependency) -> ::std::result::Result<&'a Slice, String> {
+        let name = dependency.name();
+        let mut constraints = self.constraints.iter()
+                                              .chain(self.met.iter())
+                                              .filter(|c| c.dependency.name() == name)
+                                              .collect::<Vec<_>>();
+        constraints.dedup();
+        let requested = self.requested.iter().find(|s| s.name() == name);
+        let candidates = match requested {
+            Some(requested) => vec![*requested],
+            None => {
+                self.slices.iter().filter(|s| s.name() == name).map(|s| &**s).collect::<Vec<_>>()
+            }
+        };
+        if candidates.is_empty() {
+            let dependent = self.path.last().unwrap();
+            return Err(format!("{}-{} depends on {}, but it is missing", dependent.name(),
+                               dependent.version(), dependency));
+        }
+        let satisfying = candidates.iter()
+                                   .filter(|s| {
+                                       constraints.iter()
+                                                  .all(|c| c.dependency.matches(s.version()))
+                                   })
+                                   .collect::<Vec<_>>();
+        let is_prerelease_skipped = |s: &Slice| {
+            requested.is_none() && s.is_prerelease() &&
+            self.prerelease_policy == PrereleasePolicy::Exclude
+        };
+        let selected = satisfying.iter().filter(|s| !is_prerelease_skipped(s)).max();
+        match selected {
+            Some(selected) => Ok(*selected),
+            None => {
+                let constraints = constraints.iter()
+                                             .map(|c| {
+                                                 format!("{} (required by {}-{})", c.dependency,
+                                                         c.dependent.name(), c.dependent.version())
+                                             })
+                                             .collect::<Vec<_>>();
+                let versions = candidates.iter()
+                                         .map(|s| s.version().to_string())
+                                         .collect::<Vec<_>>();
+                let mut message = format!("No version of {} satisfies all constraints: {}. \
+                                           Available versions: {}",
+                                          name, constraints.join(", "), versions.join(", "));
+                let skipped = satisfying.iter().filter(|s| is_prerelease_skipped(s)).max();
+                if let Some(prerelease) = skipped {
+                    message.push_str(&format!(" (prerelease {}-{} is skipped, use --pre)",
+                                              prerelease.name(), prerelease.version()));
+                }
+                Err(message)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use PrereleasePolicy;
+    use slice::{Bunch, Slice};
+    use slice::dependency::Dependency;
+    use version;
+    use super::{resolve, select};
+
+    fn parse_dependencies(dependencies: Vec<&str>) -> Vec<Dependency> {
+        dependencies.into_iter().map(|d| Dependency::parse(d).unwrap()).collect()
+    }
+
+    fn create_slice(name: &str, version: &str, dependencies: Vec<&str>) -> Rc<Slice> {
+        let bunch = Bunch::new(String::from("slices"), version::zero());
+        Rc::new(Slice::new(String::from(name), version::parse(version).unwrap(), bunch,
+                           PathBuf::from(name), Vec::new(), parse_dependencies(dependencies), Vec::new(),
+                           Vec::new()))
+    }
+
+    #[test]
+    fn resolve_puts_shared_dependencies_once() {
+        let slices = vec![create_slice("update", "0", vec![]),
+                          create_slice("wget", "0", vec!["update"]),
+                          create_slice("curl", "0", vec!["update"])];
+        let order = resolve(&slices, &[&slices[1], &slices[2]], PrereleasePolicy::Exclude)
+                        .unwrap();
+        let names = order.into_iter().map(Slice::name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["update", "wget", "curl"]);
+    }
+
+    #[test]
+    fn resolve_selects_single_version_satisfying_all_constraints() {
+        let slices = vec![create_slice("ruby", "1.9.1", vec![]),
+                          create_slice("ruby", "2.2.3", vec![]),
+                          create_slice("ruby", "3.0.0", vec![]),
+                          create_slice("jekyll", "3", vec!["ruby >=2"]),
+                          create_slice("rails", "4", vec!["ruby <3"])];
+        let order = resolve(&slices, &[&slices[3], &slices[4]], PrereleasePolicy::Exclude)
+                        .unwrap();
+        let names = order.into_iter().map(|s| format!("{}-{}", s.name(), s.version()))
+                                     .collect::<Vec<_>>();
+        assert_eq!(names, vec!["ruby-2.2.3", "jekyll-3.0.0", "rails-4.0.0"]);
+    }
+
+    #[test]
+    fn resolve_reports_unsatisfiable_constraints() {
+        let slices = vec![create_slice("ruby", "1.9.1", vec![]),
+                          create_slice("ruby", "2.2.3", vec![]),
+                          create_slice("jekyll", "3", vec!["ruby >=2"]),
+                          create_slice("old", "1", vec!["ruby <2"])];
+        match resolve(&slices, &[&slices[2], &slices[3]], PrereleasePolicy::Exclude) {
+            Ok(_) => panic!("Conflicting constraints were not detected"),
+            Err(error) => {
+                let message = format!("{}", error);
+                assert!(message.contains("ruby >=2 (required by jekyll-3.0.0)"), message);
+                assert!(message.contains("ruby <2 (required by old-1.0.0)"), message);
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_drops_constraints_of_slices_which_are_not_selected() {
+        let slices = vec![create_slice("libssl", "1.0.2", vec![]),
+                          create_slice("libssl", "3.0.0", vec![]),
+                          create_slice("ruby", "2.2.3", vec!["libssl <3"]),
+                          create_slice("ruby", "3.0.0", vec!["libssl >=3"]),
+                          create_slice("jekyll", "3", vec!["ruby"]),
+                          create_slice("rails", "4", vec!["ruby <3"])];
+        let order = resolve(&slices, &[&slices[4], &slices[5]], PrereleasePolicy::Exclude)
+                        .unwrap();
+        let names = order.into_iter().map(|s| format!("{}-{}", s.name(), s.version()))
+                                     .collect::<Vec<_>>();
+        assert_eq!(names, vec!["libssl-1.0.2", "ruby-2.2.3", "jekyll-3.0.0", "rails-4.0.0"]);
+    }
+
+    #[test]
+    fn resolve_uses_requested_version() {
+        let slices = vec![create_slice("ruby", "1.9.1", vec![]),
+                          create_slice("ruby", "2.2.3", vec![]),
+                          create_slice("jekyll", "3", vec!["ruby"])];
+        let order = resolve(&slices, &[&slices[2], &slices[0]], PrereleasePolicy::Exclude)
+                        .unwrap();
+        let names = order.into_iter().map(|s| format!("{}-{}", s.name(), s.version()))
+                                     .collect::<Vec<_>>();
+        assert_eq!(names, vec!["ruby-1.9.1", "jekyll-3.0.0"]);
+    }
+
+    #[test]
+    fn resolve_prefers_release_of_dependency() {
+        let slices = vec![create_slice("ruby", "2.2.3", vec![]),
+                          create_slice("ruby", "3.0.0-rc.1", vec![]),
+                          create_slice("old", "1", vec!["ruby <2"]),
+                          create_slice("ruby", "1.9.1-beta", vec![]),
+                          create_slice("jekyll", "3", vec!["ruby"])];
+        let select = |requested: &Slice, policy| {
+            let order = resolve(&slices, &[requested], policy).unwrap();
+            order[0].version().to_string()
+        };
+        assert_eq!(select(&slices[4], PrereleasePolicy::Exclude), "2.2.3");
+        assert_eq!(select(&slices[4], PrereleasePolicy::Include), "3.0.0-rc.1");
+        assert_eq!(select(&slices[2], PrereleasePolicy::Include), "1.9.1-beta");
+    }
+
+    #[test]
+    fn resolve_skips_prerelease_of_dependency_without_release() {
+        let slices = vec![create_slice("ruby", "2.2.3", vec![]),
+                          create_slice("ruby", "1.9.1-beta", vec![]),
+                          create_slice("old", "1", vec!["ruby <2"])];
+        match resolve(&slices, &[&slices[2]], PrereleasePolicy::Exclude) {
+            Ok(order) => panic!("Prerelease was selected: {:?}", order),
+            Err(error) => {
+                let message = format!("{}", error);
+                assert!(message.contains("prerelease ruby-1.9.1-beta is skipped"), message);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_and_unsatisfied_dependencies_are_unresolved() {
+        let slices = vec![create_slice("ruby", "2.2.3", vec![]),
+                          create_slice("python", "2.7.0", vec![]),
+                          create_slice("python", "3.0.0-rc.1", vec![]),
+                          create_slice("jekyll", "3", vec!["ruby", "git", "python >=3"])];
+        let selection = select(&slices, &[&slices[3]], PrereleasePolicy::Exclude).unwrap();
+        let names = selection.order.iter().map(|s| format!("{}-{}", s.name(), s.version()))
+                                          .collect::<Vec<_>>();
+        assert_eq!(names, vec!["ruby-2.2.3", "jekyll-3.0.0"]);
+        let unresolved = selection.unresolved.iter()
+                                             .map(|u| u.dependency.to_string())
+                                             .collect::<Vec<_>>();
+        assert_eq!(unresolved, vec!["git", "python >=3"]);
+        assert!(selection.unresolved[0].reason.contains("jekyll-3.0.0 depends on git, but it is \
+                                                         missing"),
+                selection.unresolved[0].reason.clone());
+        assert!(selection.unresolved[1].reason.contains("prerelease python-3.0.0-rc.1 is skipped"),
+                selection.unresolved[1].reason.clone());
+        let selection = select(&slices, &[&slices[3]], PrereleasePolicy::Include).unwrap();
+        assert_eq!(selection.unresolved.len(), 1);
+    }
+
+    #[test]
+    fn cycle_is_reported_with_full_path() {
+        let slices = vec![create_slice("a", "1", vec!["b"]),
+                          create_slice("b", "1", vec!["c"]),
+                          create_slice("c", "1", vec!["a"])];
+        match resolve(&slices, &[&slices[0]], PrereleasePolicy::Exclude) {
+            Ok(order) => panic!("Cycle was not detected: {:?}", order),
+            Err(error) => {
+                let message = format!("{}", error);
+                assert!(message.contains("a-1.0.0 -> b-1.0.0 -> c-1.0.0 -> a-1.0.0"), message);
+            }
+        }
+        let slices = vec![create_slice("a", "1", vec!["a"])];
+        assert!(select(&slices, &[&slices[0]], PrereleasePolicy::Exclude).is_err());
+    }
+}
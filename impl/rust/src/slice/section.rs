@@ -49,6 +49,30 @@ impl FromStr for Kind {
     }
 }
 
+impl Kind {
+    /// Returns the keyword which starts the section in a slice file
+    pub fn keyword(&self) -> &'static str {
+        match *self {
+            Kind::Os => "OS",
+            Kind::Dep => "DEP",
+            Kind::Add => "ADD",
+            Kind::Cmd => "CMD",
+            Kind::Copy => "COPY",
+            Kind::EntryPoint => "ENTRYPOINT",
+            Kind::Env => "ENV",
+            Kind::Expose => "EXPOSE",
+            Kind::From => "FROM",
+            Kind::Label => "LABEL",
+            Kind::Maintainer => "MAINTAINER",
+            Kind::Onbuild => "ONBUILD",
+            Kind::Run => "RUN",
+            Kind::User => "USER",
+            Kind::Volume => "VOLUME",
+            Kind::WorkDir => "WORKDIR",
+        }
+    }
+}
+
 #[derive(Clone)]
 #[derive(Debug)]
 #[derive(PartialEq)]
@@ -58,6 +82,10 @@ pub struct Section {
 }
 
 impl Section {
+    pub fn new(kind: Kind, items: Vec<String>) -> Section {
+        Section { kind: kind, items: items }
+    }
+
     pub fn from_lines<L:Borrow<str>>(lines: Vec<L>) -> (Option<Section>, Vec<L>) {
         let mut remaining_lines = Vec::new();
         let mut items = Vec::new();
@@ -97,6 +125,16 @@ impl Section {
     }
 }
 
+#[test]
+fn keyword_is_parsed_back_to_kind() {
+    let kinds = vec![Kind::Os, Kind::Dep, Kind::Add, Kind::Cmd, Kind::Copy, Kind::EntryPoint,
+                     Kind::Env, Kind::Expose, Kind::From, Kind::Label, Kind::Maintainer,
+                     Kind::Onbuild, Kind::Run, Kind::User, Kind::Volume, Kind::WorkDir];
+    for kind in kinds {
+        assert_eq!(Kind::from_str(kind.keyword()), Ok(kind));
+    }
+}
+
 #[test]
 fn section_load_from_lines_works() {
     let mut lines: Vec<String> = Vec::new();
@@ -1,14 +1,20 @@
 use std::borrow::Borrow;
 use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use semver::Version;
+use slice::bunch::Bunch;
+use slice::dependency::Dependency;
+use slice::section::Section;
+use version::SliceVersion;
 
 #[derive(Debug)]
 pub struct Slice {
     name: String,
-	version: Version,
-	ancestors: Vec<String>,
-	preparations: Vec<String>,
+	version: SliceVersion,
+	bunch: Bunch,
+	path: PathBuf,
+	sections: Vec<Section>,
+	dependencies: Vec<Dependency>,
 	resolved_dependencies: Vec<Rc<Slice>>,
 	unresolved_dependencies: Vec<String>,
 }
@@ -16,12 +22,13 @@ pub struct Slice {
 impl Slice {
 	/// # Panics
 	/// * If `name` is empty
-    pub fn new(name: String, version: Version, ancestors: Vec<String>, preparations: Vec<String>,
+    pub fn new(name: String, version: SliceVersion, bunch: Bunch, path: PathBuf,
+	           sections: Vec<Section>, dependencies: Vec<Dependency>,
 	           resolved_dependencies: Vec<Rc<Slice>>, unresolved_dependencies: Vec<String>)
-			   -> Slice {
+	           -> Slice {
 		assert_not_empty!(name);
-		Slice { name: name, version: version, ancestors: ancestors, preparations: preparations,
-		        resolved_dependencies: resolved_dependencies,
+		Slice { name: name, version: version, bunch: bunch, path: path, sections: sections,
+		        dependencies: dependencies, resolved_dependencies: resolved_dependencies,
 				unresolved_dependencies: unresolved_dependencies }
 	}
 
@@ -29,12 +36,33 @@ impl Slice {
 	    &self.name
 	}
 
-	pub fn version(&self) -> &Version {
+	pub fn version(&self) -> &SliceVersion {
 	    &self.version
 	}
 
-	pub fn ancestors(&self) -> &Vec<String> {
-	    &self.ancestors
+	/// Bunch the slice was loaded from
+	pub fn bunch(&self) -> &Bunch {
+	    &self.bunch
+	}
+
+	/// Whether the slice or its bunch has a prerelease version
+	pub fn is_prerelease(&self) -> bool {
+	    self.version.is_prerelease() || self.bunch.version.is_prerelease()
+	}
+
+	/// Path of the file the slice was loaded from
+	pub fn path(&self) -> &Path {
+	    &self.path
+	}
+
+	/// Sections of the slice file in their original order, except `DEP`
+	pub fn sections(&self) -> &Vec<Section> {
+	    &self.sections
+	}
+
+	/// Dependencies as declared in the `DEP` section
+	pub fn dependencies(&self) -> &Vec<Dependency> {
+		&self.dependencies
 	}
 
 	pub fn resolved_dependencies(&self) -> Vec<&Slice> {
@@ -55,25 +83,23 @@ impl Slice {
 		}
 	    unresolved_dependencies
 	}
-
-	pub fn preparations(&self) -> &Vec<String> {
-	    &self.preparations
-	}
 }
 
+/// Slices are equal if their names, versions and bunch versions are equal.
+/// The bunch name is not considered.
 impl PartialEq for Slice {
     fn eq(&self, other: &Self) -> bool {
-	    self.name == other.name && self.version == other.version
+	    self.name == other.name && self.version == other.version &&
+	    self.bunch.version == other.bunch.version
 	}
 }
 
+/// Slices are ordered by bunch version, then by version, then by name
 impl PartialOrd for Slice {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-	    if self.name == other.name {
-		    self.version.partial_cmp(&other.version)
-		} else {
-		    self.name.partial_cmp(&other.name)
-		}
+	    Some(self.bunch.version.cmp(&other.bunch.version)
+	                           .then(self.version.cmp(&other.version))
+	                           .then(self.name.cmp(&other.name)))
 	}
 }
 
@@ -88,17 +114,46 @@ impl Ord for Slice {
 
 #[cfg(test)]
 mod tests {
+    use std::path::PathBuf;
     use std::rc::Rc;
-	use slice::Slice;
+	use slice::{Bunch, Kind, Section, Slice};
 	use version;
 
 	fn create_slice(name: &str, resolved_dependencies: Vec<Rc<Slice>>,
 	                unresolved_dependencies: Vec<&str>) -> Slice {
 		let unresolved_dependencies = unresolved_dependencies.into_iter().map(str::to_string)
 																	     .collect();
-		Slice::new(String::from(name), version::zero(), Vec::new(),
-		           vec![String::from("do_nothing")], resolved_dependencies,
-			       unresolved_dependencies)
+		let bunch = Bunch::new(String::from("slices"), version::zero());
+		let sections = vec![Section::new(Kind::Run, vec![String::from("do_nothing")])];
+		Slice::new(String::from(name), version::zero(), bunch, PathBuf::from(name), sections,
+		           Vec::new(), resolved_dependencies, unresolved_dependencies)
+	}
+
+	fn create_slice_with_versions(version: &str, bunch_version: &str) -> Slice {
+		let bunch = Bunch::new(String::from("slices"), version::parse(bunch_version).unwrap());
+		Slice::new(String::from("a"), version::parse(version).unwrap(), bunch, PathBuf::from("a"),
+		           Vec::new(), Vec::new(), Vec::new(), Vec::new())
+	}
+
+	#[test]
+	fn bunch_version_is_compared_first() {
+	    let mut slices = vec![create_slice_with_versions("1.0.0", "1.2.3"),
+	                          create_slice_with_versions("2.0.0", "1.2.3"),
+	                          create_slice_with_versions("1.0.0", "1.2.4-rc.1")];
+	    slices.sort();
+	    let versions = slices.iter().map(|s| (s.bunch().version.to_string(), s.version().to_string()))
+	                                .collect::<Vec<_>>();
+	    assert_eq!(versions, vec![(String::from("1.2.3"), String::from("1.0.0")),
+	                              (String::from("1.2.3"), String::from("2.0.0")),
+	                              (String::from("1.2.4-rc.1"), String::from("1.0.0"))]);
+	}
+
+	#[test]
+	fn bunch_name_is_not_considered() {
+	    let slice = create_slice_with_versions("1.0.0", "1.0.0");
+	    let mut other_slice = create_slice_with_versions("1.0.0", "1.0.0");
+	    other_slice.bunch.name = String::from("my-slices");
+	    assert!(slice == other_slice);
 	}
 
     #[test]
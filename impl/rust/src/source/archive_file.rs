@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io::Read;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use error::{Error, Result};
+use source::{ArchiveFormat, Fetched, Source};
+
+/// Local zip archive or tarball
+pub struct ArchiveFileSource {
+    path: PathBuf,
+    format: ArchiveFormat,
+}
+
+impl ArchiveFileSource {
+    /// # Errors
+    /// `Error::Usage` if the extension of the file is not a known archive extension
+    pub fn new(path: PathBuf) -> Result<ArchiveFileSource> {
+        let format = path.to_str().and_then(ArchiveFormat::from_file_name);
+        match format {
+            Some(format) => Ok(ArchiveFileSource { path: path, format: format }),
+            None => {
+                Err(Error::Usage(format!("Unknown archive format. Expected .zip, .tar, .tar.gz \
+                                          or .tgz file. Path = {}", path.display())))
+            }
+        }
+    }
+}
+
+impl Source for ArchiveFileSource {
+    fn kind(&self) -> &'static str {
+        "archive"
+    }
+
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn fetch(&self) -> Result<Fetched> {
+        let bytes = try!(read_file(&self.path));
+        Ok(Fetched::Archive(self.format, bytes))
+    }
+
+    fn fetch_companion(&self, extension: &str) -> Result<Option<Vec<u8>>> {
+        let mut path = self.path.clone().into_os_string();
+        path.push(extension);
+        match read_file(Path::new(&path)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(Error::Io(_, ref error)) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error)
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    let mut file = try!(File::open(path).map_err(|e| Error::io(path, e)));
+    let mut bytes = Vec::new();
+    try!(file.read_to_end(&mut bytes).map_err(|e| Error::io(path, e)));
+    Ok(bytes)
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+use error::Result;
+use source::{Fetched, Source};
+
+/// Local directory with bunches, e.g. a working copy of the slice repository
+pub struct DirectorySource {
+    path: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new(path: PathBuf) -> DirectorySource {
+        DirectorySource { path: path }
+    }
+}
+
+impl Source for DirectorySource {
+    fn kind(&self) -> &'static str {
+        "directory"
+    }
+
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn fetch(&self) -> Result<Fetched> {
+        Ok(Fetched::Directory(self.path.clone()))
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::Path;
+use std::process;
+use error::{Error, Result};
+use helper;
+use source::{Fetched, Source};
+
+/// Branch or tag of a git repository, cloned with the `git` binary
+///
+/// The repository content is placed into the bunch directory `<repository>-<reference>`,
+/// the same way GitHub names the directories of branch archives.
+pub struct GitSource {
+    url: String,
+    reference: String,
+}
+
+impl GitSource {
+    /// Parses `[git+]<url>#<reference>`
+    /// # Errors
+    /// `Error::Usage` if the reference is missing, the bunch version is taken from it
+    pub fn from_url(url: &str) -> Result<GitSource> {
+        let url = url.trim_left_matches("git+");
+        let mut parts = url.splitn(2, '#');
+        let url = parts.next().unwrap().to_string();
+        match parts.next() {
+            Some(reference) if !reference.is_empty() => {
+                Ok(GitSource { url: url, reference: reference.to_string() })
+            }
+            _ => Err(Error::Usage(format!("Git url must name a branch or a tag with the bunch \
+                                           version after #, e.g. {}#du-0.0.2", url)))
+        }
+    }
+
+    fn bunch_directory_name(&self) -> String {
+        let repository = self.url.trim_right_matches('/').rsplit(|c| c == '/' || c == ':')
+                                                         .next().unwrap();
+        format!("{}-{}", repository.trim_right_matches(".git"), self.reference)
+    }
+
+    fn clone_into(&self, path: &Path) -> Result<()> {
+        let output = process::Command::new("git")
+                                      .arg("clone").arg("--quiet").arg("--depth").arg("1")
+                                      .arg("--branch").arg(&self.reference)
+                                      .arg(&self.url).arg(path)
+                                      .output();
+        let output = try!(output.map_err(|e| Error::io("git", e)));
+        if !output.status.success() {
+            return Err(Error::Network(format!("Cloning of {} failed: {}", self.location(),
+                                              String::from_utf8_lossy(&output.stderr).trim())));
+        }
+        let git_directory = path.join(".git");
+        fs::remove_dir_all(&git_directory).map_err(|e| Error::io(&git_directory, e))
+    }
+}
+
+impl Source for GitSource {
+    fn kind(&self) -> &'static str {
+        "git"
+    }
+
+    fn location(&self) -> String {
+        format!("{}#{}", self.url, self.reference)
+    }
+
+    fn fetch(&self) -> Result<Fetched> {
+        let directory = try!(helper::create_temporary_directory("sb-git"));
+        let result = self.clone_into(&directory.join(self.bunch_directory_name()));
+        match result {
+            Ok(()) => Ok(Fetched::TemporaryDirectory(directory)),
+            Err(error) => {
+                let _ = fs::remove_dir_all(&directory);
+                Err(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GitSource;
+
+    #[test]
+    fn bunch_directory_is_named_after_repository_and_reference() {
+        let source = GitSource::from_url("git+https://github.com/slicebuild/slices.git#du-0.0.2")
+                         .unwrap();
+        assert_eq!(source.url, "https://github.com/slicebuild/slices.git");
+        assert_eq!(source.bunch_directory_name(), "slices-du-0.0.2");
+        let source = GitSource::from_url("git@github.com:slices.git#1.0.0").unwrap();
+        assert_eq!(source.bunch_directory_name(), "slices-1.0.0");
+    }
+
+    #[test]
+    fn reference_is_required() {
+        assert!(GitSource::from_url("https://github.com/slicebuild/slices.git").is_err());
+    }
+}
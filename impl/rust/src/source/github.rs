@@ -0,0 +1,103 @@
+use rustc_serialize::json::Json;
+use error::{Error, Result};
+use source::{ArchiveFormat, Fetched, Source};
+use source::http;
+use version;
+
+const BRANCHES_URI: &'static str = "https://api.github.com/repos/slicebuild/slices/branches";
+
+/// The official slice repository, its latest versioned branch is downloaded
+pub struct GithubSource;
+
+impl GithubSource {
+    pub fn new() -> GithubSource {
+        GithubSource
+    }
+
+    fn determine_latest_version() -> Result<String> {
+        let uri = BRANCHES_URI;
+        let body = try!(http::get(uri));
+        let body = try!(String::from_utf8(body).map_err(|_| {
+            Error::Parse(format!("Response from {} is not valid UTF-8", uri))
+        }));
+        let json = try!(Json::from_str(&body).map_err(|error| {
+            Error::Parse(format!("Response from {} is not valid JSON: {}", uri, error))
+        }));
+        let array = try!(json.as_array().ok_or_else(|| {
+            Error::Parse(format!("Expected array, but received {}", json))
+        }));
+        let mut versions = Vec::new();
+        for item in array {
+            let field = "name".to_string();
+            let name = match item.as_object() {
+                Some(obj) => {
+                    match obj.get(&field) {
+                        Some(name) => {
+                            match name.as_string() {
+                                Some(name) => name,
+                                None => {
+                                    return Err(Error::Parse(format!("{} is not a string", field)))
+                                }
+                            }
+                        },
+                        None => {
+                            return Err(Error::Parse(format!("Object has no \"{}\" field", field)))
+                        }
+                    }
+                }
+                None => return Err(Error::Parse(format!("Expected object, but received {}", item)))
+            };
+            if name != "master" {
+                versions.push(name);
+            }
+        }
+        if versions.is_empty() {
+            return Err(Error::Resolution(format!("There are no slice versions at {}", uri)));
+        }
+        choose_latest_version(&versions).map(str::to_string)
+    }
+}
+
+impl Source for GithubSource {
+    fn kind(&self) -> &'static str {
+        "github"
+    }
+
+    fn location(&self) -> String {
+        String::from("https://github.com/slicebuild/slices")
+    }
+
+    fn fetch(&self) -> Result<Fetched> {
+        let version = try!(GithubSource::determine_latest_version());
+        println!("Version = {}", version);
+        let uri = format!("https://codeload.github.com/slicebuild/slices/zip/{}", version);
+        let bytes = try!(http::get(&uri));
+        Ok(Fetched::Archive(ArchiveFormat::Zip, bytes))
+    }
+}
+
+/// # Panics
+/// Panics for empty versions
+fn choose_latest_version<'a>(versions: &'a Vec<&str>) -> Result<&'a str> {
+    assert_not_empty!(versions);
+    let mut latest = Vec::new();
+    for (i, v) in versions.iter().enumerate() {
+        let (_, version) = try!(version::extract_name_and_version(v));
+        latest.push((version, i));
+    }
+    let (_, i) = latest.into_iter().max().unwrap();
+    Ok(versions[i])
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn choose_latest_version() {
+        let versions = vec!["du-0.0.1-rc.1",
+                            "du-0.0.2",
+                            "my-du-1.0.0",
+                            "ubuntu-only-1.0.1",
+                            "fed-2.1.1"];
+        assert_eq!(super::choose_latest_version(&versions).unwrap(), "fed-2.1.1");
+    }
+}
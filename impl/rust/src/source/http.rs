@@ -0,0 +1,62 @@
+use curl::http;
+use error::{Error, Result};
+use source::{ArchiveFormat, Fetched, Source};
+
+/// Archive downloaded over HTTP(S), its format is determined by the extension and is zip
+/// by default
+pub struct HttpSource {
+    url: String,
+}
+
+impl HttpSource {
+    pub fn new(url: String) -> HttpSource {
+        HttpSource { url: url }
+    }
+}
+
+impl Source for HttpSource {
+    fn kind(&self) -> &'static str {
+        "http"
+    }
+
+    fn location(&self) -> String {
+        self.url.clone()
+    }
+
+    fn fetch(&self) -> Result<Fetched> {
+        let path = self.url.split(|c| c == '?' || c == '#').next().unwrap();
+        let format = ArchiveFormat::from_file_name(path).unwrap_or(ArchiveFormat::Zip);
+        let bytes = try!(get(&self.url));
+        Ok(Fetched::Archive(format, bytes))
+    }
+
+    fn fetch_companion(&self, extension: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.url.split(|c| c == '?' || c == '#').next().unwrap();
+        get_if_exists(&format!("{}{}", path, extension))
+    }
+}
+
+/// Downloads the body of `uri`
+/// # Errors
+/// `Error::Network` if the request fails or the response status is not 200
+pub fn get(uri: &str) -> Result<Vec<u8>> {
+    match try!(get_if_exists(uri)) {
+        Some(body) => Ok(body),
+        None => Err(Error::Network(format!("Request to {} failed with status 404", uri)))
+    }
+}
+
+/// Downloads the body of `uri`, `None` is returned if the response status is 404
+fn get_if_exists(uri: &str) -> Result<Option<Vec<u8>>> {
+    let mut handle = http::handle();
+    let request = handle.get(uri).header("user-agent", "Mozilla/4.0 (compatible)");
+    match request.exec() {
+        Ok(ref response) if response.get_code() == 404 => Ok(None),
+        Ok(ref response) if response.get_code() != 200 => {
+            Err(Error::Network(format!("Request to {} failed with status {}", uri,
+                                       response.get_code())))
+        }
+        Ok(response) => Ok(Some(response.move_body())),
+        Err(error) => Err(Error::Network(format!("Request to {} failed: {}", uri, error)))
+    }
+}
@@ -0,0 +1,397 @@
+//! Installation of fetched slices into the slice root directory
+//!
+//! Slices are unpacked into a staging directory next to the slice root directory first.
+//! Only when everything is unpacked, every top level entry of the staging directory replaces
+//! the entry with the same name in the slice root directory, so a broken archive never leaves
+//! a half-written bunch behind.
+
+use std::fs;
+use std::fs::{create_dir_all, File};
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use flate2::read::GzDecoder;
+use tar;
+use zip::read::ZipArchive;
+use error::{Error, Result};
+use source::{ArchiveFormat, Fetched};
+
+/// Unpacks `fetched` and swaps its bunches into `slice_root_directory`
+/// # Returns
+/// Names of the installed bunches
+/// # Errors
+/// * `Error::Archive` if the archive cannot be read, is empty or has an entry whose path
+///   leaves the extraction directory
+/// * `Error::Io` if a file cannot be written or moved
+pub fn install(fetched: Fetched, slice_root_directory: &Path) -> Result<Vec<String>> {
+    let staging_directory = try!(create_staging_directory(slice_root_directory));
+    let new_directory = staging_directory.join("new");
+    let previous_directory = staging_directory.join("previous");
+    let result = unpack(fetched, &new_directory).and_then(|_| {
+        swap_in(&new_directory, &previous_directory, slice_root_directory)
+    });
+    let _ = fs::remove_dir_all(&staging_directory);
+    result
+}
+
+/// The staging directory is a sibling of `slice_root_directory` to be on the same file system,
+/// otherwise entries cannot be renamed into place
+fn create_staging_directory(slice_root_directory: &Path) -> Result<PathBuf> {
+    let name = slice_root_directory.file_name().and_then(|n| n.to_str()).unwrap_or("slices");
+    let parent = slice_root_directory.parent().unwrap_or(slice_root_directory);
+    let path = parent.join(format!(".{}-staging", name));
+    if path.exists() {
+        try!(fs::remove_dir_all(&path).map_err(|e| Error::io(&path, e)));
+    }
+    try!(create_dir_all(path.join("new")).map_err(|e| Error::io(&path, e)));
+    try!(create_dir_all(path.join("previous")).map_err(|e| Error::io(&path, e)));
+    Ok(path)
+}
+
+fn unpack(fetched: Fetched, directory: &Path) -> Result<()> {
+    match fetched {
+        Fetched::Archive(ArchiveFormat::Zip, bytes) => extract_zip(bytes, directory),
+        Fetched::Archive(ArchiveFormat::Tar, bytes) => {
+            extract_tarball(Cursor::new(bytes), directory)
+        }
+        Fetched::Archive(ArchiveFormat::TarGz, bytes) => {
+            extract_tarball(GzDecoder::new(Cursor::new(bytes)), directory)
+        }
+        Fetched::Directory(path) => copy_directory(&path, directory),
+        Fetched::TemporaryDirectory(path) => {
+            let result = copy_directory(&path, directory);
+            let _ = fs::remove_dir_all(&path);
+            result
+        }
+    }
+}
+
+/// Moves the entries of `new_directory` into `slice_root_directory`. An entry being replaced
+/// is moved into `previous_directory` first. If any entry cannot be moved, every entry moved
+/// so far is moved back and the replaced entries are restored.
+fn swap_in(new_directory: &Path, previous_directory: &Path, slice_root_directory: &Path)
+           -> Result<Vec<String>> {
+    let mut entries = try!(read_dir_entries(new_directory));
+    if entries.is_empty() {
+        return Err(Error::Archive(String::from("There are no bunches in the fetched slices")));
+    }
+    entries.sort_by_key(|entry| entry.file_name());
+    try!(create_dir_all(slice_root_directory).map_err(|e| Error::io(slice_root_directory, e)));
+    let mut swapped: Vec<SwappedEntry> = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let swapped_entry = SwappedEntry {
+            new: entry.path(),
+            target: slice_root_directory.join(entry.file_name()),
+            previous: previous_directory.join(entry.file_name()),
+            has_previous: fs::symlink_metadata(slice_root_directory.join(entry.file_name()))
+                              .is_ok(),
+        };
+        if let Err(error) = swapped_entry.swap() {
+            for swapped_entry in swapped.iter().rev() {
+                swapped_entry.roll_back();
+            }
+            return Err(error);
+        }
+        swapped.push(swapped_entry);
+    }
+    Ok(entries.iter().map(|entry| entry.file_name().to_string_lossy().into_owned()).collect())
+}
+
+/// Entry of the staging directory which replaces the entry with the same name in the slice
+/// root directory
+struct SwappedEntry {
+    new: PathBuf,
+    target: PathBuf,
+    previous: PathBuf,
+    has_previous: bool,
+}
+
+impl SwappedEntry {
+    /// Moves the replaced entry out and the new one in. If the new entry cannot be moved in,
+    /// the replaced one is restored.
+    fn swap(&self) -> Result<()> {
+        if self.has_previous {
+            try!(fs::rename(&self.target, &self.previous).map_err(|e| {
+                Error::io(&self.target, e)
+            }));
+        }
+        if let Err(error) = fs::rename(&self.new, &self.target) {
+            if self.has_previous {
+                let _ = fs::rename(&self.previous, &self.target);
+            }
+            return Err(Error::io(&self.target, error));
+        }
+        Ok(())
+    }
+
+    /// Moves the new entry back to the staging directory and restores the replaced one
+    fn roll_back(&self) {
+        let _ = fs::rename(&self.target, &self.new);
+        if self.has_previous {
+            let _ = fs::rename(&self.previous, &self.target);
+        }
+    }
+}
+
+fn extract_zip(bytes: Vec<u8>, directory: &Path) -> Result<()> {
+    let mut zip_archive = try!(ZipArchive::new(Cursor::new(bytes)).map_err(|error| {
+        Error::Archive(format!("Downloaded file is not a zip archive: {}", error))
+    }));
+    for i in 0..zip_archive.len() {
+        let mut file = try!(zip_archive.by_index(i).map_err(|error| {
+            Error::Archive(format!("Entry {} cannot be read: {}", i, error))
+        }));
+        let path = try!(entry_path(directory, file.name()));
+        if file.name().ends_with('/') {
+            try!(create_dir_all(&path).map_err(|e| Error::io(&path, e)));
+            continue;
+        }
+        let mut bytes: Vec<u8> = Vec::with_capacity(file.size() as usize);
+        try!(file.read_to_end(&mut bytes).map_err(|error| {
+            Error::Archive(format!("Entry {} cannot be read: {}", i, error))
+        }));
+        try!(write_file(&path, &bytes, file.unix_mode()));
+    }
+    Ok(())
+}
+
+/// Regular files and directories are extracted, links and special files are skipped
+fn extract_tarball<R: Read>(reader: R, directory: &Path) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    let entries = try!(archive.entries().map_err(|error| {
+        Error::Archive(format!("Tarball cannot be read: {}", error))
+    }));
+    for (i, entry) in entries.enumerate() {
+        let entry_error = |error| {
+            Error::Archive(format!("Entry {} cannot be read: {}", i, error))
+        };
+        let mut entry = try!(entry.map_err(&entry_error));
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            continue;
+        }
+        let name = try!(entry.path().map_err(&entry_error)).to_string_lossy().into_owned();
+        let path = try!(entry_path(directory, &name));
+        if entry_type.is_dir() {
+            try!(create_dir_all(&path).map_err(|e| Error::io(&path, e)));
+            continue;
+        }
+        let mut bytes = Vec::new();
+        try!(entry.read_to_end(&mut bytes).map_err(&entry_error));
+        try!(write_file(&path, &bytes, entry.header().mode().ok()));
+    }
+    Ok(())
+}
+
+fn copy_directory(source: &Path, destination: &Path) -> Result<()> {
+    for entry in try!(read_dir_entries(source)) {
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let path = entry.path();
+        let target = destination.join(entry.file_name());
+        let file_type = try!(entry.file_type().map_err(|e| Error::io(&path, e)));
+        if file_type.is_dir() {
+            try!(create_dir_all(&target).map_err(|e| Error::io(&target, e)));
+            try!(copy_directory(&path, &target));
+        } else {
+            try!(fs::copy(&path, &target).map_err(|e| Error::io(&target, e)));
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the path of an archive entry inside `directory`
+/// # Errors
+/// `Error::Archive` if the path is empty, absolute or leaves `directory` through `..`
+fn entry_path(directory: &Path, name: &str) -> Result<PathBuf> {
+    let mut path = directory.to_path_buf();
+    let mut is_empty = true;
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => {
+                path.push(part);
+                is_empty = false;
+            }
+            Component::CurDir => {}
+            _ => {
+                return Err(Error::Archive(format!("Entry path = {} leaves the extraction \
+                                                   directory", name)))
+            }
+        }
+    }
+    if is_empty {
+        return Err(Error::Archive(format!("Entry path = \"{}\" is empty", name)));
+    }
+    Ok(path)
+}
+
+fn write_file(path: &Path, bytes: &[u8], mode: Option<u32>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        try!(create_dir_all(parent).map_err(|e| Error::io(parent, e)));
+    }
+    let mut file = try!(File::create(path).map_err(|e| Error::io(path, e)));
+    try!(file.write_all(bytes).map_err(|e| Error::io(path, e)));
+    set_mode(path, mode)
+}
+
+/// Applies the permission bits of `mode`, the file type, setuid, setgid and sticky bits
+/// are dropped
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: Option<u32>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    match mode {
+        Some(mode) => {
+            let permissions = fs::Permissions::from_mode(mode & 0o777);
+            fs::set_permissions(path, permissions).map_err(|e| Error::io(path, e))
+        }
+        None => Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+fn set_mode(_: &Path, _: Option<u32>) -> Result<()> {
+    Ok(())
+}
+
+fn read_dir_entries(path: &Path) -> Result<Vec<fs::DirEntry>> {
+    let mut entries = Vec::new();
+    for entry in try!(fs::read_dir(path).map_err(|e| Error::io(path, e))) {
+        entries.push(try!(entry.map_err(|e| Error::io(path, e))));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::{Cursor, Read, Write};
+    use std::path::{Path, PathBuf};
+    use tar;
+    use zip::{CompressionMethod, ZipWriter};
+    use zip::write::FileOptions;
+    use error::Error;
+    use helper;
+    use source::{ArchiveFormat, Fetched};
+
+    fn create_zip(entries: Vec<(&str, &str)>) -> Fetched {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+            writer.start_file(name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        Fetched::Archive(ArchiveFormat::Zip, writer.finish().unwrap().into_inner())
+    }
+
+    /// The name is written into the header directly because the builder refuses `..`
+    fn create_tarball(entries: Vec<(&str, &str, u32)>) -> Fetched {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content, mode) in entries {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(content.len() as u64);
+            header.set_mode(mode);
+            header.set_cksum();
+            builder.append(&header, content.as_bytes()).unwrap();
+        }
+        Fetched::Archive(ArchiveFormat::Tar, builder.into_inner().unwrap())
+    }
+
+    fn create_slice_root_directory() -> PathBuf {
+        let directory = helper::create_temporary_directory("sb-install").unwrap();
+        directory.join("slices")
+    }
+
+    fn read_file(path: &Path) -> String {
+        let mut content = String::new();
+        File::open(path).unwrap().read_to_string(&mut content).unwrap();
+        content
+    }
+
+    fn write_file(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+    }
+
+    fn assert_archive_error(fetched: Fetched, slice_root_directory: &Path) {
+        match super::install(fetched, slice_root_directory) {
+            Err(Error::Archive(_)) => {}
+            result => panic!("Archive error expected, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn bunch_is_installed_and_replaces_previous_one() {
+        let slice_root_directory = create_slice_root_directory();
+        write_file(&slice_root_directory.join("slices-1.0.0/a/removed"), "RUN\ntrue");
+        let fetched = create_zip(vec![("slices-1.0.0/", ""), ("slices-1.0.0/_/debian-8", "FROM")]);
+        super::install(fetched, &slice_root_directory).unwrap();
+        assert_eq!(read_file(&slice_root_directory.join("slices-1.0.0/_/debian-8")), "FROM");
+        assert!(!slice_root_directory.join("slices-1.0.0/a/removed").exists());
+        let parent = slice_root_directory.parent().unwrap();
+        assert!(!parent.join(".slices-staging").exists());
+        fs::remove_dir_all(parent).unwrap();
+    }
+
+    #[test]
+    fn failed_swap_restores_every_bunch_swapped_before() {
+        let slice_root_directory = create_slice_root_directory();
+        let parent = slice_root_directory.parent().unwrap().to_path_buf();
+        let (new, previous) = (parent.join("new"), parent.join("previous"));
+        for name in &["slices-1.0.0", "slices-2.0.0"] {
+            write_file(&slice_root_directory.join(name).join("_/debian-8"), "old");
+            write_file(&new.join(name).join("_/debian-8"), "new");
+        }
+        // A non-empty directory cannot be replaced by a rename, so the second swap fails
+        write_file(&previous.join("slices-2.0.0/_/debian-8"), "blocking");
+        assert!(super::swap_in(&new, &previous, &slice_root_directory).is_err());
+        for name in &["slices-1.0.0", "slices-2.0.0"] {
+            let path = slice_root_directory.join(name).join("_/debian-8");
+            assert_eq!(read_file(&path), "old");
+        }
+        assert_eq!(read_file(&new.join("slices-1.0.0/_/debian-8")), "new");
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn path_traversal_is_rejected_and_previous_bunch_is_kept() {
+        let slice_root_directory = create_slice_root_directory();
+        let parent = slice_root_directory.parent().unwrap().to_path_buf();
+        write_file(&slice_root_directory.join("slices-1.0.0/_/debian-8"), "FROM");
+        let fetched = create_zip(vec![("slices-1.0.0/_/debian-8", "changed"),
+                                      ("slices-1.0.0/../../evil", "")]);
+        assert_archive_error(fetched, &slice_root_directory);
+        let fetched = create_tarball(vec![("slices-1.0.0/_/debian-8", "changed", 0o644),
+                                          ("/tmp/evil", "", 0o644)]);
+        assert_archive_error(fetched, &slice_root_directory);
+        assert_eq!(read_file(&slice_root_directory.join("slices-1.0.0/_/debian-8")), "FROM");
+        assert!(!parent.join("evil").exists());
+        assert!(!parent.join(".slices-staging").exists());
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn empty_archive_is_an_error() {
+        let slice_root_directory = create_slice_root_directory();
+        assert_archive_error(create_zip(Vec::new()), &slice_root_directory);
+        fs::remove_dir_all(slice_root_directory.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_modes_are_preserved() {
+        use std::os::unix::fs::PermissionsExt;
+        let slice_root_directory = create_slice_root_directory();
+        let fetched = create_tarball(vec![("slices-1.0.0/_/debian-8", "FROM", 0o4755),
+                                          ("slices-1.0.0/_/ubuntu-14", "FROM", 0o600)]);
+        super::install(fetched, &slice_root_directory).unwrap();
+        let mode = |name: &str| {
+            let path = slice_root_directory.join("slices-1.0.0/_").join(name);
+            fs::metadata(path).unwrap().permissions().mode() & 0o7777
+        };
+        assert_eq!(mode("debian-8"), 0o755);
+        assert_eq!(mode("ubuntu-14"), 0o600);
+        fs::remove_dir_all(slice_root_directory.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+//! Places slices are fetched from
+//!
+//! Every source provides a tree whose top level directories are bunches, e.g.
+//! `slices-du-0.0.2/_/debian-8.2`. The tree is either an archive or a directory.
+
+pub use self::archive_file::ArchiveFileSource;
+pub use self::directory::DirectorySource;
+pub use self::git::GitSource;
+pub use self::github::GithubSource;
+pub use self::http::HttpSource;
+
+pub mod install;
+pub mod verify;
+
+mod archive_file;
+mod directory;
+mod git;
+mod github;
+mod http;
+
+use std::path::{Path, PathBuf};
+use error::{Error, Result};
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Determines the format by the extension of `file_name`
+    pub fn from_file_name(file_name: &str) -> Option<ArchiveFormat> {
+        if file_name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if file_name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Tree of bunches provided by a source
+pub enum Fetched {
+    Archive(ArchiveFormat, Vec<u8>),
+    /// Directory owned by the user, it is only read
+    Directory(PathBuf),
+    /// Directory created by the source, it is removed once the slices are installed
+    TemporaryDirectory(PathBuf),
+}
+
+pub trait Source {
+    /// Kind of the source, e.g. `http` or `git`
+    fn kind(&self) -> &'static str;
+
+    /// Location of the slices for messages
+    fn location(&self) -> String;
+
+    fn fetch(&self) -> Result<Fetched>;
+
+    /// Downloads a file published beside the archive, e.g. `<archive>.sha256` for `.sha256`
+    /// # Returns
+    /// `None` if the source publishes no such file
+    fn fetch_companion(&self, extension: &str) -> Result<Option<Vec<u8>>> {
+        let _ = extension;
+        Ok(None)
+    }
+}
+
+/// Selects the source for `url`:
+/// * an empty url is the official slice repository on GitHub
+/// * `git+<url>`, `git://`, `git@` and urls ending with `.git` are git repositories,
+///   a branch or a tag is given after `#`
+/// * other `http://` and `https://` urls are archives
+/// * `file://` urls and plain paths are directories or archives
+///
+/// # Errors
+/// `Error::Usage` if the url points to neither a directory nor a file with a known archive
+/// extension
+pub fn from_url(url: &str) -> Result<Box<Source>> {
+    if url.is_empty() {
+        return Ok(Box::new(GithubSource::new()));
+    }
+    if is_git_url(url) {
+        return Ok(Box::new(try!(GitSource::from_url(url))));
+    }
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return Ok(Box::new(HttpSource::new(url.to_string())));
+    }
+    let path = Path::new(if url.starts_with("file://") { &url[7..] } else { url });
+    if path.is_dir() {
+        Ok(Box::new(DirectorySource::new(path.to_path_buf())))
+    } else if path.is_file() {
+        Ok(Box::new(try!(ArchiveFileSource::new(path.to_path_buf()))))
+    } else {
+        Err(Error::Usage(format!("Slice source is not found. Url = {}", url)))
+    }
+}
+
+fn is_git_url(url: &str) -> bool {
+    let url_without_reference = url.split('#').next().unwrap();
+    url.starts_with("git+") || url.starts_with("git://") || url.starts_with("git@") ||
+    url_without_reference.ends_with(".git")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::ArchiveFormat;
+
+    fn kind_of(url: &str) -> &'static str {
+        super::from_url(url).unwrap().kind()
+    }
+
+    #[test]
+    fn archive_format_from_file_name() {
+        assert_eq!(ArchiveFormat::from_file_name("a.zip"), Some(ArchiveFormat::Zip));
+        assert_eq!(ArchiveFormat::from_file_name("a.tar.gz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_file_name("a.tgz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_file_name("a.tar"), Some(ArchiveFormat::Tar));
+        assert_eq!(ArchiveFormat::from_file_name("a.rar"), None);
+    }
+
+    #[test]
+    fn url_selects_source() {
+        let mut test_slices = env::current_dir().expect("Current dir is not set");
+        test_slices.push("test_slices");
+        let test_slices = test_slices.to_str().unwrap();
+        assert_eq!(kind_of(""), "github");
+        assert_eq!(kind_of("https://example.com/slices.tar.gz"), "http");
+        assert_eq!(kind_of("https://github.com/slicebuild/slices.git#du-0.0.2"), "git");
+        assert_eq!(kind_of("git+file:///srv/slices#du-0.0.2"), "git");
+        assert_eq!(kind_of(test_slices), "directory");
+        assert_eq!(kind_of(&format!("file://{}", test_slices)), "directory");
+    }
+
+    #[test]
+    fn nonexistent_path_is_an_error() {
+        assert!(super::from_url("/path/that/does/not/exist").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Verification of fetched archives before they are extracted
+//!
+//! A source may publish a checksum manifest `<archive>.sha256` in the format of `sha256sum` and
+//! a detached OpenPGP signature `<archive>.sig` beside the archive. The manifest is checked
+//! according to the `ChecksumPolicy`. Signatures are checked with `gpgv` against the trust
+//! store, a keyring in the root directory. Once the trust store exists, slices are fetched
+//! from signed archives only: every archive must be signed by one of its keys, and directory
+//! and git sources, which cannot be signed, are rejected.
+
+use std::fs;
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use ChecksumPolicy;
+use error::{Error, Result};
+use helper;
+use source::{Fetched, Source};
+
+/// Result of a successful verification
+#[derive(Debug)]
+pub struct Verification {
+    /// SHA-256 of the archive as lowercase hex
+    pub digest: String,
+    /// Whether the signature of the archive was checked against the trust store
+    pub signed: bool,
+}
+
+/// Checks `fetched` against the checksum manifest and signature published by `source`
+/// # Returns
+/// `None` if nothing was checked
+/// # Errors
+/// `Error::Verification` if the checksum or signature does not match, or if the policy or the
+/// trust store requires a manifest or signature which the source does not publish. Directory
+/// and git sources are not archives and cannot be signed, so they are rejected once the trust
+/// store exists.
+pub fn verify(source: &Source, fetched: &Fetched, policy: ChecksumPolicy, trust_store: &Path)
+              -> Result<Option<Verification>> {
+    let bytes = match *fetched {
+        Fetched::Archive(_, ref bytes) => bytes,
+        _ if policy == ChecksumPolicy::Require => {
+            return Err(Error::Verification(format!("Slices from {} source {} have no checksum",
+                                                   source.kind(), source.location())));
+        }
+        _ if trust_store.exists() => {
+            return Err(Error::Verification(format!("Slices from {} source {} cannot be signed \
+                                                    but the trust store {} exists",
+                                                   source.kind(), source.location(),
+                                                   trust_store.display())));
+        }
+        _ => return Ok(None),
+    };
+    let digest = helper::sha256_hex(bytes);
+    let mut checked = false;
+    if policy != ChecksumPolicy::Skip {
+        match try!(source.fetch_companion(".sha256")) {
+            Some(manifest) => {
+                let expected = try!(parse_manifest(&manifest, &archive_name(source)));
+                if expected != digest {
+                    return Err(Error::Verification(format!("Checksum of {} does not match \
+                                                            the manifest. Expected = {}, \
+                                                            actual = {}",
+                                                           source.location(), expected,
+                                                           digest)));
+                }
+                checked = true;
+            }
+            None if policy == ChecksumPolicy::Require => {
+                return Err(Error::Verification(format!("There is no checksum manifest {}.sha256",
+                                                       source.location())));
+            }
+            None => {}
+        }
+    }
+    let signed = trust_store.exists();
+    if signed {
+        let signature = match try!(source.fetch_companion(".sig")) {
+            Some(signature) => signature,
+            None => {
+                return Err(Error::Verification(format!("There is no signature {}.sig but the \
+                                                        trust store {} exists",
+                                                       source.location(),
+                                                       trust_store.display())));
+            }
+        };
+        try!(check_signature(bytes, &signature, trust_store, &source.location()));
+    }
+    if checked || signed {
+        Ok(Some(Verification { digest: digest, signed: signed }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Writes the digest of a verified archive beside `bunch_path` as `<bunch>.sha256`
+pub fn record(verification: &Verification, source: &Source, bunch_path: &Path) -> Result<()> {
+    let path = record_path(bunch_path);
+    let mut file = try!(File::create(&path).map_err(|e| Error::io(&path, e)));
+    try!(write!(file, "{}  {}\n", verification.digest, source.location())
+             .map_err(|e| Error::io(&path, e)));
+    Ok(())
+}
+
+/// Removes the `<bunch>.sha256` beside `bunch_path` left by an earlier verified fetch, so
+/// unverified content has no digest recorded
+pub fn remove_record(bunch_path: &Path) -> Result<()> {
+    let path = record_path(bunch_path);
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(ref error) if error.kind() == ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(Error::io(&path, error)),
+    }
+}
+
+fn record_path(bunch_path: &Path) -> PathBuf {
+    let mut path = bunch_path.to_path_buf().into_os_string();
+    path.push(".sha256");
+    PathBuf::from(path)
+}
+
+fn archive_name(source: &Source) -> String {
+    let location = source.location();
+    let path = location.split(|c| c == '?' || c == '#').next().unwrap();
+    String::from(path.rsplit(|c| c == '/' || c == '\\').next().unwrap())
+}
+
+/// Finds the checksum of `archive_name` in a manifest which is either a bare checksum or
+/// lines of `<checksum>  <file name>`
+fn parse_manifest(manifest: &[u8], archive_name: &str) -> Result<String> {
+    let text = try!(String::from_utf8(manifest.to_vec()).map_err(|_| {
+        Error::Verification(String::from("Checksum manifest is not UTF-8"))
+    }));
+    let entries: Vec<(&str, Option<&str>)> = text.lines().map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut parts = line.splitn(2, char::is_whitespace);
+            let checksum = parts.next().unwrap();
+            let name = parts.next().map(|name| name.trim_left().trim_left_matches('*'));
+            (checksum, name)
+        })
+        .collect();
+    let checksum = entries.iter().find(|&&(_, name)| name == Some(archive_name))
+                          .or_else(|| if entries.len() == 1 { entries.first() } else { None })
+                          .map(|&(checksum, _)| checksum.to_lowercase());
+    match checksum {
+        Some(ref checksum) if checksum.len() == 64 &&
+                              checksum.chars().all(|c| c.is_digit(16)) => Ok(checksum.clone()),
+        Some(checksum) => {
+            Err(Error::Verification(format!("Checksum manifest has an invalid SHA-256 \
+                                             checksum. Checksum = {}", checksum)))
+        }
+        None => {
+            Err(Error::Verification(format!("Checksum manifest has no entry for {}",
+                                            archive_name)))
+        }
+    }
+}
+
+fn check_signature(bytes: &[u8], signature: &[u8], trust_store: &Path, location: &str)
+                   -> Result<()> {
+    let directory = try!(helper::create_temporary_directory("sb-verify"));
+    let result = write_and_check_signature(bytes, signature, trust_store, location, &directory);
+    let _ = fs::remove_dir_all(&directory);
+    result
+}
+
+fn write_and_check_signature(bytes: &[u8], signature: &[u8], trust_store: &Path,
+                             location: &str, directory: &Path)
+                             -> Result<()> {
+    let data_path = directory.join("archive");
+    let signature_path = directory.join("archive.sig");
+    for &(path, content) in &[(&data_path, bytes), (&signature_path, signature)] {
+        let mut file = try!(File::create(path).map_err(|e| Error::io(path, e)));
+        try!(file.write_all(content).map_err(|e| Error::io(path, e)));
+    }
+    let output = try!(process::Command::new("gpgv").arg("--keyring").arg(trust_store)
+                                                   .arg(&signature_path).arg(&data_path)
+                                                   .output()
+                                                   .map_err(|e| Error::io("gpgv", e)));
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::Verification(format!("Signature of {} is not valid for the trust store {}. \
+                                         {}",
+                                        location, trust_store.display(),
+                                        String::from_utf8_lossy(&output.stderr).trim())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::Path;
+    use ChecksumPolicy;
+    use error::Error;
+    use helper;
+    use source::Source;
+    use source::archive_file::ArchiveFileSource;
+    use source::directory::DirectorySource;
+    use super::{parse_manifest, verify};
+
+    const ARCHIVE_CHECKSUM: &'static str = "2cf24dba5fb0a30e26e83b2ac5b9e29e\
+                                            1b161e5c1fa7425e73043362938b9824";
+
+    fn write_file(path: &Path, bytes: &[u8]) {
+        File::create(path).unwrap().write_all(bytes).unwrap();
+    }
+
+    fn verify_archive(manifest: Option<&str>, policy: ChecksumPolicy, trust_store: bool)
+                      -> Result<bool, Error> {
+        let directory = helper::create_temporary_directory("sb-verify-test").unwrap();
+        let path = directory.join("slices.zip");
+        write_file(&path, b"hello");
+        if let Some(manifest) = manifest {
+            write_file(&directory.join("slices.zip.sha256"), manifest.as_bytes());
+        }
+        let trust_store_path = directory.join("trusted.gpg");
+        if trust_store {
+            write_file(&trust_store_path, b"");
+        }
+        let source = ArchiveFileSource::new(path).unwrap();
+        let fetched = source.fetch().unwrap();
+        let result = verify(&source, &fetched, policy, &trust_store_path);
+        fs::remove_dir_all(&directory).unwrap();
+        result.map(|verification| verification.is_some())
+    }
+
+    #[test]
+    fn sha256_hex_is_lowercase_hex() {
+        assert_eq!(helper::sha256_hex(b"hello"), ARCHIVE_CHECKSUM);
+    }
+
+    #[test]
+    fn parse_manifest_accepts_bare_checksum() {
+        let manifest = format!("{}\n", ARCHIVE_CHECKSUM.to_uppercase());
+        assert_eq!(parse_manifest(manifest.as_bytes(), "slices.zip").unwrap(), ARCHIVE_CHECKSUM);
+    }
+
+    #[test]
+    fn parse_manifest_selects_line_of_archive() {
+        let manifest = format!("{}  other.zip\n{} *slices.zip\n", "0".repeat(64),
+                               ARCHIVE_CHECKSUM);
+        assert_eq!(parse_manifest(manifest.as_bytes(), "slices.zip").unwrap(), ARCHIVE_CHECKSUM);
+        assert!(parse_manifest(manifest.as_bytes(), "missing.zip").is_err());
+    }
+
+    #[test]
+    fn parse_manifest_rejects_invalid_checksum() {
+        assert!(parse_manifest(b"abc  slices.zip", "slices.zip").is_err());
+    }
+
+    #[test]
+    fn verify_accepts_matching_checksum() {
+        let manifest = format!("{}  slices.zip\n", ARCHIVE_CHECKSUM);
+        assert_eq!(verify_archive(Some(&manifest), ChecksumPolicy::Auto, false).unwrap(), true);
+    }
+
+    #[test]
+    fn verify_rejects_mismatching_checksum() {
+        let manifest = format!("{}  slices.zip\n", "0".repeat(64));
+        match verify_archive(Some(&manifest), ChecksumPolicy::Auto, false) {
+            Err(Error::Verification(..)) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(verify_archive(Some(&manifest), ChecksumPolicy::Skip, false).unwrap(), false);
+    }
+
+    #[test]
+    fn verify_requires_manifest_if_policy_requires_it() {
+        assert_eq!(verify_archive(None, ChecksumPolicy::Auto, false).unwrap(), false);
+        assert!(verify_archive(None, ChecksumPolicy::Require, false).is_err());
+    }
+
+    #[test]
+    fn verify_requires_signature_if_trust_store_exists() {
+        let manifest = format!("{}  slices.zip\n", ARCHIVE_CHECKSUM);
+        match verify_archive(Some(&manifest), ChecksumPolicy::Auto, true) {
+            Err(Error::Verification(ref message)) => assert!(message.contains("signature")),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn verify_rejects_directory_if_trust_store_exists() {
+        let directory = helper::create_temporary_directory("sb-verify-test").unwrap();
+        let trust_store_path = directory.join("trusted.gpg");
+        let source = DirectorySource::new(directory.clone());
+        let fetched = source.fetch().unwrap();
+        let unsigned = verify(&source, &fetched, ChecksumPolicy::Auto, &trust_store_path);
+        write_file(&trust_store_path, b"");
+        let rejected = verify(&source, &fetched, ChecksumPolicy::Auto, &trust_store_path);
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(unsigned.unwrap().is_none());
+        match rejected {
+            Err(Error::Verification(ref message)) => assert!(message.contains("trust store")),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+}
@@ -1,14 +1,180 @@
+use std::fmt::{Display, Formatter, Result as FormatResult};
 use std::str::FromStr;
-use semver::Version;
+use semver::{Identifier, Version};
+use VersionMatchStrategy;
+use error::{Error, Result};
 
-pub fn parse(str: &str) -> Version {
-    match Version::parse(str) {
-        Ok(version) => version,
-        Err(_) => parse_invalid_version(str)
+/// Operators of requests in the order they are tried
+const OPERATORS: [(&'static str, VersionMatchStrategy); 4] = [
+    ("<=", VersionMatchStrategy::ExactOrLesser),
+    (">=", VersionMatchStrategy::ExactOrGreater),
+    ("=", VersionMatchStrategy::Exact),
+    ("^", VersionMatchStrategy::Compatible),
+];
+
+/// Stage of a prerelease, releases are greater than every prerelease
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+#[derive(PartialOrd)]
+#[derive(Ord)]
+#[derive(Hash)]
+pub enum Prerelease {
+    Alpha,
+    Beta,
+    Rc,
+    Release,
+}
+
+impl Prerelease {
+    /// Value of the prerelease place: alpha = -3, beta = -2, rc = -1 and release = 0
+    pub fn place(&self) -> i64 {
+        match *self {
+            Prerelease::Alpha => -3,
+            Prerelease::Beta => -2,
+            Prerelease::Rc => -1,
+            Prerelease::Release => 0,
+        }
+    }
+
+    fn tag(&self) -> Option<&'static str> {
+        match *self {
+            Prerelease::Alpha => Some("alpha"),
+            Prerelease::Beta => Some("beta"),
+            Prerelease::Rc => Some("rc"),
+            Prerelease::Release => None,
+        }
+    }
+}
+
+/// Version of a slice, a bunch or the tool in the five places of `doc/slices-versioning.md`:
+/// major, minor, patch, prerelease and iteration. Missing places are zero, so `2` is
+/// `2.0.0.0.0` and `1.2.4-rc.1` is `1.2.4.-1.1`. Versions are ordered place by place.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+#[derive(PartialOrd)]
+#[derive(Ord)]
+#[derive(Hash)]
+pub struct SliceVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub prerelease: Prerelease,
+    /// Number after the prerelease tag, always zero for releases
+    pub iteration: u64,
+}
+
+impl SliceVersion {
+    pub fn new(major: u64, minor: u64, patch: u64) -> SliceVersion {
+        SliceVersion { major: major, minor: minor, patch: patch, prerelease: Prerelease::Release,
+                       iteration: 0 }
+    }
+
+    /// Parses `major[.minor[.patch]][-(alpha|beta|rc)[.iteration]]`, an empty string is zero
+    /// # Errors
+    /// `Error::Parse` naming the invalid part, e.g. a fourth number, an unknown prerelease tag
+    /// or build metadata
+    pub fn parse(str: &str) -> Result<SliceVersion> {
+        let invalid_version = |reason: String| {
+            Error::Parse(format!("Invalid version = {}: {}", str, reason))
+        };
+        if str.is_empty() {
+            return Ok(zero());
+        }
+        if str.contains('+') {
+            return Err(invalid_version(String::from("build metadata is not supported")));
+        }
+        let (release, prerelease) = match str.find('-') {
+            Some(position) => (&str[..position], Some(&str[position + 1..])),
+            None => (str, None),
+        };
+        let numbers = release.split('.').collect::<Vec<_>>();
+        if numbers.len() > 3 {
+            return Err(invalid_version(format!("expected at most 3 numbers before the \
+                                                prerelease, found {}",
+                                               numbers.len())));
+        }
+        let mut places = [0; 3];
+        for (place, number) in places.iter_mut().zip(&numbers) {
+            *place = try!(parse_number(number).map_err(&invalid_version));
+        }
+        let mut version = SliceVersion::new(places[0], places[1], places[2]);
+        if let Some(prerelease) = prerelease {
+            let parts = prerelease.split('.').collect::<Vec<_>>();
+            if parts.len() > 2 {
+                return Err(invalid_version(format!("expected at most 2 prerelease places, \
+                                                    found {}",
+                                                   parts.len())));
+            }
+            version.prerelease = match parts[0] {
+                "alpha" => Prerelease::Alpha,
+                "beta" => Prerelease::Beta,
+                "rc" => Prerelease::Rc,
+                tag => {
+                    return Err(invalid_version(format!("unknown prerelease = \"{}\". Available \
+                                                        prereleases = [alpha, beta, rc]",
+                                                       tag)));
+                }
+            };
+            if let Some(iteration) = parts.get(1) {
+                version.iteration = try!(parse_number(iteration).map_err(&invalid_version));
+            }
+        }
+        Ok(version)
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        self.prerelease != Prerelease::Release
+    }
+
+    /// Returns the five places, e.g. `[1, 2, 4, -1, 1]` for `1.2.4-rc.1`
+    pub fn places(&self) -> [i64; 5] {
+        [self.major as i64, self.minor as i64, self.patch as i64, self.prerelease.place(),
+         self.iteration as i64]
+    }
+
+    /// Converts to the semver version which `DEP` requirements are matched against
+    pub fn to_semver(&self) -> Version {
+        let pre = match self.prerelease.tag() {
+            Some(tag) => vec![Identifier::AlphaNumeric(tag.to_string()),
+                              Identifier::Numeric(self.iteration)],
+            None => Vec::new(),
+        };
+        Version { major: self.major, minor: self.minor, patch: self.patch, pre: pre,
+                  build: Vec::new() }
+    }
+}
+
+impl Display for SliceVersion {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        try!(write!(formatter, "{}.{}.{}", self.major, self.minor, self.patch));
+        match self.prerelease.tag() {
+            Some(tag) if self.iteration > 0 => write!(formatter, "-{}.{}", tag, self.iteration),
+            Some(tag) => write!(formatter, "-{}", tag),
+            None => Ok(()),
+        }
     }
 }
 
-pub fn extract_name_and_version(string: &str) -> (String, Version) {
+impl FromStr for SliceVersion {
+    type Err = Error;
+
+    fn from_str(str: &str) -> Result<SliceVersion> {
+        SliceVersion::parse(str)
+    }
+}
+
+/// # Errors
+/// `Error::Parse` if `str` is not a valid version, see `SliceVersion::parse`
+pub fn parse(str: &str) -> Result<SliceVersion> {
+    SliceVersion::parse(str)
+}
+
+pub fn extract_name_and_version(string: &str) -> Result<(String, SliceVersion)> {
     assert_not_empty!(string);
     let iter = string.chars().enumerate();
     let positions = iter.filter(|&(_, c)| c == '-')
@@ -21,96 +187,211 @@ pub fn extract_name_and_version(string: &str) -> (String, Version) {
         }
                         })
                         .collect::<Vec<_>>();
-    match positions.len() {
-        0 => (string.to_string(), zero()),
-        _ => {
-            let pos = *positions.last().unwrap();
-            (string[..pos].to_string(), parse(&string[pos + 1..]))
+    match positions.last() {
+        None => Ok((string.to_string(), zero())),
+        Some(&pos) => {
+            let version = try!(parse(&string[pos + 1..]));
+            Ok((string[..pos].to_string(), version))
         }
     }
 }
 
-pub fn zero() -> Version {
-    Version { major: 0, minor: 0, patch: 0, pre: Vec::new(), build: Vec::new() }
+/// Splits a request like `ruby<=2.2`, `ruby@latest` or `ruby-2.2` into the name, version and
+/// the strategy the version is matched with. The version of `name@latest` is zero.
+/// # Errors
+/// `Error::Parse` if the name or the version after an operator is empty or invalid
+pub fn parse_request(request: &str) -> Result<(String, SliceVersion, VersionMatchStrategy)> {
+    let invalid_request = |reason: &str| {
+        Error::Parse(format!("Invalid request = {}: {}", request, reason))
+    };
+    let request = request.trim();
+    let operator_position = request.find(|c| c == '<' || c == '>' || c == '=' || c == '^' ||
+                                             c == '@');
+    let position = match operator_position {
+        Some(position) => position,
+        None if request.is_empty() => return Err(invalid_request("name is empty")),
+        None => {
+            let (name, version) = try!(extract_name_and_version(request));
+            return Ok((name, version, VersionMatchStrategy::ExactOrGreater));
+        }
+    };
+    let name = request[..position].trim();
+    if name.is_empty() {
+        return Err(invalid_request("name is empty"));
+    }
+    let rest = &request[position..];
+    if rest.starts_with('@') {
+        return match rest[1..].trim() {
+            "latest" => Ok((name.to_string(), zero(), VersionMatchStrategy::Latest)),
+            _ => Err(invalid_request("only @latest is supported")),
+        };
+    }
+    for &(operator, strategy) in &OPERATORS {
+        if rest.starts_with(operator) {
+            let version = rest[operator.len()..].trim();
+            if version.is_empty() {
+                return Err(invalid_request("version is empty"));
+            }
+            return Ok((name.to_string(), try!(parse(version)), strategy));
+        }
+    }
+    Err(invalid_request("unknown operator. Available operators = [=, <=, >=, ^, @latest]"))
 }
 
-fn parse_invalid_version(str: &str) -> Version {
-    if str.is_empty() {
-        return zero();
+/// Checks `version` against `requested_version` with `strategy`
+pub fn matches(version: &SliceVersion, requested_version: &SliceVersion,
+               strategy: VersionMatchStrategy)
+               -> bool {
+    match strategy {
+        VersionMatchStrategy::Exact => version == requested_version,
+        VersionMatchStrategy::ExactOrLesser => version <= requested_version,
+        VersionMatchStrategy::ExactOrGreater => version >= requested_version,
+        VersionMatchStrategy::Compatible => {
+            let same_leading_component = if requested_version.major > 0 {
+                version.major == requested_version.major
+            } else if requested_version.minor > 0 {
+                version.major == 0 && version.minor == requested_version.minor
+            } else {
+                version.major == 0 && version.minor == 0 &&
+                version.patch == requested_version.patch
+            };
+            same_leading_component && version >= requested_version
+        }
+        VersionMatchStrategy::Latest => true,
     }
-    match str.to_string().chars().filter(|&c| c == '.').count() {
-        0 => Version { major: u64::from_str(str).unwrap(), .. zero() },
-        1 => {
-            let parts = str.split('.').collect::<Vec<_>>();
-            let major = u64::from_str(parts[0]).unwrap();
-            let minor = u64::from_str(parts[1]).unwrap();
-            Version { major : major, minor: minor, .. zero() }
-        },
-        _ => panic!("Invalid version string = {}", str)
+}
+
+/// Version of the tool, which determines compatible bunches of slices
+pub fn tool() -> SliceVersion {
+    parse(env!("CARGO_PKG_VERSION")).expect("Package version is valid")
+}
+
+pub fn zero() -> SliceVersion {
+    SliceVersion::new(0, 0, 0)
+}
+
+fn parse_number(part: &str) -> ::std::result::Result<u64, String> {
+    if part.is_empty() || !part.chars().all(|c| c.is_digit(10)) {
+        return Err(format!("\"{}\" is not a number", part));
     }
+    u64::from_str(part).map_err(|_| format!("{} is too large", part))
 }
 
 #[cfg(test)]
 mod tests {
     use semver::{Identifier, Version};
+    use super::{Prerelease, SliceVersion};
+    use VersionMatchStrategy;
+
+    #[test]
+    fn parse_request_with_operators() {
+        let requests = [("ruby=2.2.3", "2.2.3", VersionMatchStrategy::Exact),
+                        ("ruby<=2.2", "2.2.0", VersionMatchStrategy::ExactOrLesser),
+                        ("ruby >= 2", "2.0.0", VersionMatchStrategy::ExactOrGreater),
+                        ("ruby^2", "2.0.0", VersionMatchStrategy::Compatible),
+                        ("ruby@latest", "0.0.0", VersionMatchStrategy::Latest),
+                        ("ruby-2.2", "2.2.0", VersionMatchStrategy::ExactOrGreater)];
+        for &(request, version, strategy) in &requests {
+            let (name, parsed_version, parsed_strategy) = super::parse_request(request).unwrap();
+            assert_eq!((name.as_str(), parsed_version.to_string(), parsed_strategy),
+                       ("ruby", version.to_string(), strategy));
+        }
+    }
+
+    #[test]
+    fn parse_invalid_requests() {
+        for request in &["", "=2", "ruby=", "ruby@2", "ruby<2", "ruby=x.y"] {
+            assert!(super::parse_request(request).is_err(), "{} is accepted", request);
+        }
+    }
+
+    #[test]
+    fn compatible_versions_keep_first_non_zero_component() {
+        let matches = |version: &str, requested_version: &str| {
+            super::matches(&super::parse(version).unwrap(),
+                           &super::parse(requested_version).unwrap(),
+                           VersionMatchStrategy::Compatible)
+        };
+        assert!(matches("2.9.1", "2.1"));
+        assert!(!matches("3.0.0", "2.1"));
+        assert!(!matches("2.0.9", "2.1"));
+        assert!(matches("0.2.5", "0.2.1"));
+        assert!(!matches("0.3.0", "0.2.1"));
+        assert!(!matches("0.0.4", "0.0.3"));
+    }
 
     #[test]
     fn slice_with_only_major() {
-        let (name, version) = super::extract_name_and_version("apache-2");
+        let (name, version) = super::extract_name_and_version("apache-2").unwrap();
         assert_eq!(name, "apache");
-        assert_eq!(version, super::parse("2"));
+        assert_eq!(version, super::parse("2").unwrap());
     }
 
     #[test]
     fn slice_with_dash_in_name() {
-        let (name, version) = super::extract_name_and_version("my-apache-2");
+        let (name, version) = super::extract_name_and_version("my-apache-2").unwrap();
         assert_eq!(name, "my-apache");
-        assert_eq!(version, super::parse("2"));
+        assert_eq!(version, super::parse("2").unwrap());
     }
 
     #[test]
     fn get_slice_name_and_version_from_string() {
         let string = "my_app-2.0.0-beta".to_string();
-        let (slice_name, version) = super::extract_name_and_version(&string);
+        let (slice_name, version) = super::extract_name_and_version(&string).unwrap();
         assert_eq!(slice_name, "my_app");
-        assert_eq!(version, super::parse("2.0.0-beta"));
+        assert_eq!(version, super::parse("2.0.0-beta").unwrap());
     }
 
     #[test]
-    fn only_major() {
-        let version = Version { major: 1, minor: 0, patch: 0, pre: Vec::new(),
-                                build: Vec::new() };
-        assert_eq!(super::parse("1"), version);
+    fn missing_places_are_zero() {
+        assert_eq!(super::parse("1").unwrap(), SliceVersion::new(1, 0, 0));
+        assert_eq!(super::parse("1.2").unwrap(), SliceVersion::new(1, 2, 0));
+        assert_eq!(super::parse("1.2.3").unwrap(), SliceVersion::new(1, 2, 3));
+        assert_eq!(super::parse("2").unwrap().places(), [2, 0, 0, 0, 0]);
     }
 
     #[test]
-    fn major_and_minor() {
-        let version = Version { major: 1, minor: 2, patch: 0, pre: Vec::new(),
-                                build: Vec::new() };
-        assert_eq!(super::parse("1.2"), version);
+    fn prerelease_has_tag_and_iteration() {
+        let version = super::parse("1.2.4-rc.1").unwrap();
+        assert_eq!(version, SliceVersion { prerelease: Prerelease::Rc, iteration: 1,
+                                           .. SliceVersion::new(1, 2, 4) });
+        assert_eq!(version.places(), [1, 2, 4, -1, 1]);
+        assert!(version.is_prerelease());
+        assert_eq!(super::parse("1.0.0-alpha").unwrap(), super::parse("1.0.0-alpha.0").unwrap());
+        assert_eq!(super::parse("1.0.0-alpha.0").unwrap().to_string(), "1.0.0-alpha");
+        assert_eq!(version.to_string(), "1.2.4-rc.1");
     }
 
     #[test]
-    fn major_and_minor_and_patch() {
-        let version = Version { major: 1, minor: 2, patch: 3, pre: Vec::new(),
-                                build: Vec::new() };
-        assert_eq!(super::parse("1.2.3"), version);
+    fn prereleases_precede_release() {
+        let versions = ["1.0.0-alpha", "1.0.0-alpha.2", "1.0.0-beta", "1.0.0-beta.10", "1.0.0-rc.1",
+                        "1.0.0", "1.0.1-alpha", "1.1"];
+        for pair in versions.windows(2) {
+            assert!(super::parse(pair[0]).unwrap() < super::parse(pair[1]).unwrap(),
+                    "{} < {}", pair[0], pair[1]);
+        }
     }
 
     #[test]
-    fn major_and_minor_and_patch_and_prerelease() {
+    fn semver_has_the_same_prerelease() {
         let version = Version { major: 1, minor: 2, patch: 3,
-                                pre: vec![Identifier::AlphaNumeric("alpha".to_string())],
+                                pre: vec![Identifier::AlphaNumeric("alpha".to_string()),
+                                          Identifier::Numeric(5)],
                                 build: Vec::new() };
-        assert_eq!(super::parse("1.2.3-alpha"), version);
+        assert_eq!(super::parse("1.2.3-alpha.5").unwrap().to_semver(), version);
+        assert!(super::parse("1.2.3").unwrap().to_semver().pre.is_empty());
     }
 
     #[test]
-    fn major_and_minor_and_patch_and_prerelease_and_build() {
-        let version = Version { major: 1, minor: 2, patch: 3,
-                                pre: vec![Identifier::AlphaNumeric("alpha".to_string()),
-                                          Identifier::Numeric(5)],
-                                build: Vec::new() };
-        assert_eq!(super::parse("1.2.3-alpha.5"), version);
+    fn invalid_versions_are_errors() {
+        for version in &["1.2.3.4", "1.0.0-gamma", "1.0.0-alpha.1.2", "1.0.0+build", "1..2",
+                         "1.0.0-", "1.0.0-beta.x", "-1"] {
+            assert!(super::parse(version).is_err(), "{} is accepted", version);
+        }
+    }
+
+    #[test]
+    fn non_numeric_part_is_an_error() {
+        assert!(super::extract_name_and_version("ruby-2.x").is_err());
     }
 }
\ No newline at end of file